@@ -3,12 +3,19 @@ use std::{
     sync::Arc,
 };
 
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    formula::Formula,
     number::Number,
-    raw_data::{EnergyType, FuelCategory, RecipeMode, UPS},
+    raw_data::{
+        DumpFormat, EnergyType, FuelCategory, MaxLevel, Modifier, RecipeMode, ResearchTrigger,
+        TechnologyCount, Temperature, UPS,
+    },
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Category {
     BurnableFuelEnergy(FuelCategory),
     PickaxeMining,
@@ -17,18 +24,27 @@ pub enum Category {
     Research,
     Generator(Arc<str>),
     Boiler(Arc<str>),
+    OffshorePump(Arc<str>),
+    RocketLaunch(Arc<str>),
     Free,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum Item {
     Item {
         name: Arc<str>,
     },
     Energy {
-        fuel_category: Option<crate::raw_data::FuelCategory>,
+        /// Sorted categories the energy can be burnt from, `None` for non-burner energy
+        fuel_categories: Option<Arc<[FuelCategory]>>,
         energy_type: crate::raw_data::EnergyType,
     },
+    /// Fluid heated above its default temperature, like steam
+    Fluid {
+        name: Arc<str>,
+        temperature: Number<Temperature>,
+    },
 }
 
 impl From<&str> for Item {
@@ -40,68 +56,447 @@ impl From<&str> for Item {
 impl Item {
     pub fn name(&self) -> &Arc<str> {
         match self {
-            Item::Item { name } => name,
+            Item::Item { name } | Item::Fluid { name, .. } => name,
             Item::Energy { .. } => panic!("energy item is fake item, no name for you, sorry"),
         }
     }
-    pub fn is(&self, test_name: &str) -> bool {
-        match self {
-            Item::Item { name } => &**name == test_name,
-            Item::Energy { .. } => false,
+
+    /// Fluids at their default temperature are plain items
+    pub fn fluid(fluid: &crate::raw_data::Fluid, temperature: Number<Temperature>) -> Self {
+        if temperature == fluid.default_temperature {
+            Item::Item {
+                name: fluid.name.arc(),
+            }
+        } else {
+            Item::Fluid {
+                name: fluid.name.arc(),
+                temperature,
+            }
         }
     }
 }
 
-#[derive(Debug)]
+/// Item or fluid burnable in some energy source
+struct Fuel {
+    item: Arc<str>,
+    category: FuelCategory,
+    /// Joules per item
+    value: Number,
+    burnt_result: Option<Arc<str>>,
+}
+
+/// Items listed more than once add up
+fn sum_amounts<'a>(
+    list: impl IntoIterator<Item = &'a crate::raw_data::AmountOf>,
+    amount: impl Fn(&crate::raw_data::AmountOf) -> Number,
+) -> HashMap<Item, Number> {
+    let mut amounts = HashMap::new();
+    for entry in list {
+        *amounts
+            .entry(Item::Item {
+                name: entry.name.arc(),
+            })
+            .or_default() += amount(entry);
+    }
+    amounts
+}
+
+/// Name of the recipe burning `item` for a burner accepting `categories`
+pub fn burnable_fuel_energy_recipe(item: &str, categories: &[FuelCategory]) -> Arc<str> {
+    format!("{item:?} {categories:?} burnable fuel energy").into()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recipe {
     pub name: Arc<str>,
     pub category: Category,
     pub ingredients: HashMap<Item, Number>,
     pub results: HashMap<Item, Number>,
+    /// Whether productivity from modules and machines applies
+    pub allow_productivity: bool,
+    /// Parts of the results not affected by productivity
+    pub catalysts: HashMap<Item, Number>,
     /// `None` = instant
     pub crafting_time: Option<Number>,
 }
 
+impl Recipe {
+    /// Gross amount of `item` produced by one craft
+    pub fn result_amount(&self, item: &Item, productivity: Number) -> Number {
+        let Some(&amount) = self.results.get(item) else {
+            return Number::new(0.0);
+        };
+        let catalyst = std::cmp::min(
+            amount,
+            self.catalysts.get(item).copied().unwrap_or_default(),
+        );
+        (amount - catalyst) * (Number::from(1) + productivity) + catalyst
+    }
+
+    /// Amount of `item` gained by one craft, minus what the craft consumes of it
+    pub fn net_yield(&self, item: &Item, productivity: Number) -> Number {
+        self.result_amount(item, productivity)
+            - self.ingredients.get(item).copied().unwrap_or_default()
+    }
+}
+
 pub const CHARACTER_MINING: &str = "character mining";
 pub const CHARACTER_CRAFTING: &str = "character crafting";
 pub const FREE_STUFF: &str = "free";
+/// Fake item produced by launching a rocket
+pub const ROCKET_LAUNCH: &str = "rocket launch";
+/// Roughly how long the rocket takes from lifting off to leaving the surface
+const ROCKET_FLIGHT_TICKS: f64 = 1800.0;
+/// Where everything happens, recipes and machines not allowed here are left out
+const PLANET: &str = "nauvis";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MachineKind {
+    CharacterMining,
+    CharacterCrafting,
+    MiningDrill,
+    Lab,
+    /// Output at full daylight, in watts
+    SolarPanel {
+        production: Number,
+    },
+    /// Stores solar energy for the night, in joules
+    Accumulator {
+        buffer_capacity: Number,
+    },
+    /// Output bonus per neighbouring reactor
+    Reactor {
+        neighbour_bonus: Number,
+    },
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ModuleEffects {
+    pub speed: Number,
+    pub productivity: Number,
+    pub consumption: Number,
+}
+
+impl ModuleEffects {
+    fn from_raw(effect: &crate::raw_data::ModuleEffect) -> Self {
+        let bonus = |value: Option<crate::raw_data::ModuleEffectValue>| {
+            value.map_or(Number::new(0.0), |value| value.bonus)
+        };
+        Self {
+            speed: bonus(effect.speed),
+            productivity: bonus(effect.productivity),
+            consumption: bonus(effect.consumption),
+        }
+    }
+
+    fn add(&mut self, other: &Self, times: Number) {
+        self.speed += other.speed * times;
+        self.productivity += other.productivity * times;
+        self.consumption += other.consumption * times;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Module {
+    pub name: Arc<str>,
+    pub effects: ModuleEffects,
+    /// `None` = can be used for any recipe
+    pub limitation: Option<HashSet<Arc<str>>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beacon {
+    pub name: Arc<str>,
+    pub module_slots: usize,
+    pub distribution_effectivity: Number,
+    pub energy_usage: HashMap<Item, Number>,
+    /// `None` = everything allowed
+    pub allowed_effects: Option<HashSet<Arc<str>>>,
+}
+
+/// Modules inserted into the machine and the beacons around it
+#[derive(Debug, Clone, Default)]
+pub struct ModuleSetup {
+    pub modules: Vec<(Arc<str>, usize)>,
+    pub beacon: Option<Arc<str>>,
+    /// How many beacons affect each machine
+    pub beacons: usize,
+    pub beacon_modules: Vec<(Arc<str>, usize)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MachineModules {
+    pub slots: usize,
+    pub effects: ModuleEffects,
+    /// Built into the machine, applies regardless of the module limitation
+    pub base_effects: ModuleEffects,
+    /// Recipes the modules work for, `None` = any
+    pub limitation: Option<HashSet<Arc<str>>>,
+    /// Used by the beacons around the machine, modules do not change it
+    pub beacon_energy_usage: HashMap<Item, Number>,
+}
+
+impl MachineModules {
+    pub fn new(slots: usize) -> Self {
+        Self {
+            slots,
+            ..Default::default()
+        }
+    }
+
+    fn from_raw(modules: &crate::raw_data::MachineModules) -> Self {
+        Self {
+            base_effects: modules
+                .effect_receiver
+                .as_ref()
+                .map(|receiver| ModuleEffects::from_raw(&receiver.base_effect))
+                .unwrap_or_default(),
+            ..Self::new(modules.slots())
+        }
+    }
+
+    /// `recipe` is `None` when the limitation does not apply
+    fn effects_for(&self, recipe: Option<&str>) -> ModuleEffects {
+        let mut effects = self.base_effects;
+        match (&self.limitation, recipe) {
+            (Some(limitation), Some(recipe)) if !limitation.contains(recipe) => {}
+            _ => effects.add(&self.effects, 1.into()),
+        }
+        effects
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Machine {
     pub name: Arc<str>,
+    pub kind: MachineKind,
+    pub modules: MachineModules,
     pub categories: HashSet<Category>,
     pub energy_usage: HashMap<Item, Number>,
     pub crafting_speed: Number,
 }
 
-#[derive(Debug)]
+impl Machine {
+    pub fn module_effects(&self, recipe: &str) -> ModuleEffects {
+        match self.kind {
+            // limitations list item recipes, mining and research are always allowed
+            MachineKind::MiningDrill | MachineKind::Lab => self.modules.effects_for(None),
+            _ => self.modules.effects_for(Some(recipe)),
+        }
+    }
+
+    /// Energy per second while crafting `recipe`, including the beacons around it
+    pub fn energy_usage_for(&self, recipe: &str) -> HashMap<Item, Number> {
+        // https://wiki.factorio.com/Module#Effects
+        let consumption = std::cmp::max(
+            Number::new(0.2),
+            Number::from(1) + self.module_effects(recipe).consumption,
+        );
+        let mut usage: HashMap<Item, Number> = self
+            .energy_usage
+            .iter()
+            .map(|(energy_item, &usage)| (energy_item.clone(), usage * consumption))
+            .collect();
+        for (energy_item, &beacon_usage) in &self.modules.beacon_energy_usage {
+            *usage.entry(energy_item.clone()).or_default() += beacon_usage;
+        }
+        usage
+    }
+}
+
+/// Research bonuses, all of them are additive
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Effect {
+    MiningDrillProductivity(Number),
+    LaboratorySpeed(Number),
+    LaboratoryProductivity(Number),
+    CharacterMiningSpeed(Number),
+    CharacterCraftingSpeed(Number),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ResearchCount {
+    Const(Number),
+    Formula(Formula),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Research {
     pub name: Arc<str>,
+    /// Name without the level suffix, `mining-productivity` for `mining-productivity-4`
+    pub base_name: Arc<str>,
     pub dependencies: Vec<Arc<str>>,
     pub recipe: Arc<str>,
+    pub level: usize,
+    /// `None` = infinite
+    pub max_level: Option<usize>,
+    pub count: ResearchCount,
+    /// Applied for every level researched
+    pub effects: Vec<Effect>,
+    pub unit_ingredients: HashMap<Item, Number>,
+    /// `None` for 2.0 trigger technologies, which need no labs
+    pub unit_time: Option<Number>,
+    pub ignore_tech_cost_multiplier: bool,
 }
 
-#[derive(Debug)]
+impl Research {
+    pub fn has_level(&self, level: usize) -> bool {
+        self.level <= level && self.max_level.is_none_or(|max_level| level <= max_level)
+    }
+
+    pub fn level_name(&self, level: usize) -> Arc<str> {
+        if level == self.level {
+            self.name.clone()
+        } else {
+            format!("{}-{level}", self.base_name).into()
+        }
+    }
+
+    pub fn recipe_name(&self, level: usize) -> Arc<str> {
+        if level == self.level {
+            self.recipe.clone()
+        } else {
+            format!("research {:?} level {level}", self.name).into()
+        }
+    }
+
+    pub fn count(&self, level: usize) -> Number {
+        match &self.count {
+            ResearchCount::Const(count) => *count,
+            ResearchCount::Formula(formula) => formula.eval(level),
+        }
+    }
+
+    pub fn research_recipe(&self, level: usize, science_multiplier: Number) -> Recipe {
+        let count = self.count(level)
+            * if self.ignore_tech_cost_multiplier {
+                Number::new(1.0)
+            } else {
+                science_multiplier
+            };
+        Recipe {
+            name: self.recipe_name(level),
+            category: Category::Research,
+            ingredients: self
+                .unit_ingredients
+                .iter()
+                .map(|(ingredient, &amount)| (ingredient.clone(), amount * count))
+                .collect(),
+            results: HashMap::new(),
+            allow_productivity: true,
+            catalysts: HashMap::new(),
+            crafting_time: self.unit_time.map(|unit_time| unit_time * count),
+        }
+    }
+}
+
+fn restrict(limitation: &mut Option<HashSet<Arc<str>>>, other: &Option<HashSet<Arc<str>>>) {
+    let Some(other) = other else {
+        return;
+    };
+    *limitation = Some(match limitation.take() {
+        Some(current) => current.intersection(other).cloned().collect(),
+        None => other.clone(),
+    });
+}
+
+/// `mining-productivity-4` is level 4 of `mining-productivity`
+pub fn split_level(name: &str) -> (&str, Option<usize>) {
+    if let Some((base_name, level)) = name.rsplit_once('-') {
+        if let Ok(level) = level.parse() {
+            return (base_name, Some(level));
+        }
+    }
+    (name, None)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub recipe_mode: RecipeMode,
+    pub technology_price_multiplier: Number,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Data {
     pub recipes: HashMap<Arc<str>, Recipe>,
     pub machines: HashMap<Arc<str>, Machine>,
     pub researches: HashMap<Arc<str>, Research>,
+    pub modules: HashMap<Arc<str>, Module>,
+    pub beacons: HashMap<Arc<str>, Beacon>,
+    pub presets: HashMap<Arc<str>, Preset>,
+    pub science_multiplier: Number,
+    /// Recipes not enabled from the start -> technologies unlocking them
+    pub locked_recipes: HashMap<Arc<str>, Vec<Arc<str>>>,
+}
+
+/// Exact bits of the multiplier, its `Debug` output is rounded
+fn cache_key(dump_hash: u64, mode: RecipeMode, science_multiplier: Number) -> String {
+    format!(
+        "{dump_hash:016x}-{mode:?}-{:016x}",
+        science_multiplier.value().to_bits()
+    )
 }
 
 impl Data {
-    pub fn new(mode: RecipeMode, science_multiplier: Number) -> anyhow::Result<Self> {
-        // running `factorio --dump-data`
-        // will create `~/.factorio/script-output/data-raw-dump.json`
-        let raw = crate::raw_data::Data::from_reader(std::io::BufReader::new(
-            std::fs::File::open("data-raw-dump.json")?,
-        ))?;
+    /// `path` is the output of `factorio --dump-data`, see [`crate::dump::find`]
+    pub fn new(
+        path: &std::path::Path,
+        mode: RecipeMode,
+        science_multiplier: Number,
+    ) -> anyhow::Result<Self> {
+        let cache_key = cache_key(crate::dump::hash(path)?, mode, science_multiplier);
+        if let Some(data) = crate::dump::load_cached(&cache_key) {
+            return Ok(data);
+        }
+        let dump = std::fs::File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+        let raw = crate::raw_data::Data::from_reader(std::io::BufReader::new(dump))
+            .with_context(|| format!("Failed to parse {path:?}"))?;
+        let data = Self::from_raw(raw, mode, science_multiplier)?;
+        crate::dump::save_cached(&cache_key, &data);
+        Ok(data)
+    }
 
+    fn from_raw(
+        raw: crate::raw_data::Data,
+        mode: RecipeMode,
+        science_multiplier: Number,
+    ) -> anyhow::Result<Self> {
+        log::info!("Loading a {:?} dump", raw.format);
+        if raw.format == DumpFormat::V2_0 && mode == RecipeMode::Expensive {
+            anyhow::bail!("2.0 dumps have no expensive recipes");
+        }
         let mut data = Data {
             recipes: Default::default(),
             machines: Default::default(),
             researches: Default::default(),
+            modules: Default::default(),
+            beacons: Default::default(),
+            presets: Default::default(),
+            science_multiplier,
+            locked_recipes: Default::default(),
         };
 
+        {
+            let defaults = &raw.map_settings.difficulty_settings;
+            for (name, preset) in &raw.map_gen_presets {
+                let settings = &preset.advanced_settings.difficulty_settings;
+                data.presets.insert(
+                    name.arc(),
+                    Preset {
+                        recipe_mode: settings
+                            .recipe_mode()?
+                            .or(defaults.recipe_mode()?)
+                            .unwrap_or(RecipeMode::Normal),
+                        technology_price_multiplier: settings
+                            .technology_price_multiplier
+                            .or(defaults.technology_price_multiplier)
+                            .unwrap_or(1.into()),
+                    },
+                );
+            }
+        }
+
         for simple_entity in raw.simple_entity.values() {
             if simple_entity.count_as_rock_for_filtered_deconstruction {
                 let name: Arc<str> = format!("pickaxe mine {:?}", simple_entity.name).into();
@@ -113,18 +508,9 @@ impl Data {
                         name,
                         category: Category::PickaxeMining,
                         ingredients: HashMap::new(),
-                        results: minable
-                            .results
-                            .iter()
-                            .map(|result| {
-                                (
-                                    Item::Item {
-                                        name: result.name.arc(),
-                                    },
-                                    result.amount,
-                                )
-                            })
-                            .collect(),
+                        results: sum_amounts(&minable.results, |result| result.amount),
+                        allow_productivity: true,
+                        catalysts: HashMap::new(),
                         crafting_time: Some(minable.mining_time),
                     },
                 );
@@ -134,68 +520,35 @@ impl Data {
         for recipe in raw.recipe.values() {
             let name = recipe.name.arc();
             let recipe = &recipe.modes[&mode];
+            if !raw.allowed_on(PLANET, &recipe.surface_conditions) {
+                log::debug!("Skipping recipe {name:?}, it can not be crafted on {PLANET}");
+                continue;
+            }
+            if !recipe.enabled {
+                data.locked_recipes.insert(name.clone(), Vec::new());
+            }
             data.recipes.insert(
                 name.clone(),
                 Recipe {
                     name,
                     category: Category::Craft(recipe.category.arc()),
-                    ingredients: recipe
-                        .ingredients
-                        .iter()
-                        .map(|ingredient| {
-                            (
-                                Item::Item {
-                                    name: ingredient.name.arc(),
-                                },
-                                ingredient.amount,
-                            )
-                        })
-                        .collect(),
-                    results: recipe
-                        .results
-                        .iter()
-                        .map(|result| {
-                            (
-                                Item::Item {
-                                    name: result.name.arc(),
-                                },
-                                result.amount * recipe.result_count.unwrap_or(1.into()),
-                            )
-                        })
-                        .collect(),
+                    ingredients: sum_amounts(&recipe.ingredients, |ingredient| ingredient.amount),
+                    results: sum_amounts(&recipe.results, |result| {
+                        result.amount * recipe.result_count.unwrap_or(1.into())
+                    }),
+                    allow_productivity: recipe.allow_productivity(raw.format),
+                    catalysts: sum_amounts(
+                        recipe
+                            .results
+                            .iter()
+                            .filter(|result| result.catalyst_amount.value() > 0.0),
+                        |result| result.catalyst_amount,
+                    ),
                     crafting_time: Some(recipe.energy_required),
                 },
             );
         }
 
-        for item in raw.item.values() {
-            if let Some(fuel) = &item.fuel {
-                let name: Arc<str> =
-                    format!("{:?} {:?} burnable fuel energy", item.name, fuel.category).into();
-                data.recipes.insert(
-                    name.clone(),
-                    Recipe {
-                        category: Category::BurnableFuelEnergy(fuel.category),
-                        name,
-                        ingredients: HashMap::from_iter([(
-                            Item::Item {
-                                name: item.name.arc(),
-                            },
-                            1.into(),
-                        )]),
-                        results: HashMap::from_iter([(
-                            Item::Energy {
-                                fuel_category: Some(fuel.category),
-                                energy_type: crate::raw_data::EnergyType::Burner,
-                            },
-                            fuel.value.value().into(),
-                        )]),
-                        crafting_time: None,
-                    },
-                );
-            }
-        }
-
         for resource in raw.resource.values() {
             let name: Arc<str> = format!("{:?} mining", resource.name).into();
             data.recipes.insert(
@@ -216,43 +569,77 @@ impl Data {
                             )
                         })
                         .collect(),
-                    results: resource
-                        .minable
-                        .results
-                        .iter()
-                        .map(|result| {
-                            (
-                                Item::Item {
-                                    name: result.name.arc(),
-                                },
-                                result.amount,
-                            )
-                        })
-                        .collect(),
+                    results: sum_amounts(&resource.minable.results, |result| result.amount),
+                    allow_productivity: true,
+                    catalysts: HashMap::new(),
                     crafting_time: Some(resource.minable.mining_time),
                 },
             );
         }
 
+        /// `None` if the energy source can not work, the machine is left out then
         fn energy_ingredients(
+            raw: &crate::raw_data::Data,
+            owner: &crate::raw_data::Name,
             source: &crate::raw_data::EnergySource,
             usage: Number<crate::raw_data::Watts>,
-        ) -> HashMap<Item, Number> {
-            let item = Item::Energy {
-                fuel_category: source.fuel_category,
-                energy_type: source.r#type,
-            };
+        ) -> Option<HashMap<Item, Number>> {
             let amount =
                 Number::new(usage.value()) / source.effectivity.unwrap_or_else(|| 1.into());
-            HashMap::from_iter([(item, amount)])
+            let filter = source
+                .fluid_box
+                .as_ref()
+                .and_then(|fluid_box| fluid_box.filter.as_ref());
+            Some(match (source.r#type, filter) {
+                (EnergyType::Void, _) => HashMap::new(),
+                (EnergyType::Unknown, _) => {
+                    log::warn!("{owner:?} has an unknown energy source type, treating it as free");
+                    HashMap::new()
+                }
+                (EnergyType::Fluid, Some(filter)) if source.burns_fluid => {
+                    let fuel_value = raw
+                        .fluid
+                        .get(filter)
+                        .and_then(|fluid| fluid.fuel_value)
+                        .unwrap_or_default();
+                    if fuel_value.value() <= 0.0 {
+                        log::warn!(
+                            "Skipping {owner:?}, it burns {filter:?} which has no fuel value"
+                        );
+                        return None;
+                    }
+                    HashMap::from_iter([(
+                        Item::Item { name: filter.arc() },
+                        amount / Number::new(fuel_value.value()),
+                    )])
+                }
+                _ => {
+                    if source.r#type == EnergyType::Fluid && !source.burns_fluid {
+                        // nothing produces this, such machines will not be usable
+                        log::warn!("{owner:?} uses fluid heat as energy, which is not supported");
+                    }
+                    let item = Item::Energy {
+                        fuel_categories: source.fuel_categories().map(Arc::from),
+                        energy_type: source.r#type,
+                    };
+                    HashMap::from_iter([(item, amount)])
+                }
+            })
         }
 
         for drill in raw.mining_drill.values() {
             let name = drill.name.arc();
+            let Some(energy_usage) =
+                energy_ingredients(&raw, &drill.name, &drill.energy_source, drill.energy_usage)
+            else {
+                continue;
+            };
             data.machines.insert(
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::MiningDrill,
+                    modules: MachineModules::from_raw(&drill.modules),
                     categories: HashSet::from_iter(
                         drill
                             .resource_categories
@@ -260,19 +647,26 @@ impl Data {
                             .map(|resource| Category::Mining(resource.arc())),
                     ),
                     crafting_speed: drill.mining_speed,
-                    energy_usage: energy_ingredients(&drill.energy_source, drill.energy_usage),
+                    energy_usage,
                 },
             );
         }
 
         for lab in raw.lab.values() {
             let name = lab.name.arc();
+            let Some(energy_usage) =
+                energy_ingredients(&raw, &lab.name, &lab.energy_source, lab.energy_usage)
+            else {
+                continue;
+            };
             data.machines.insert(
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::Lab,
+                    modules: MachineModules::from_raw(&lab.modules),
                     categories: HashSet::from_iter([Category::Research]),
-                    energy_usage: energy_ingredients(&lab.energy_source, lab.energy_usage),
+                    energy_usage,
                     crafting_speed: lab.researching_speed,
                 },
             );
@@ -280,111 +674,457 @@ impl Data {
 
         for generator in raw.generator.values() {
             let name = generator.name.arc();
-            let recipe_name: Arc<str> = format!("generator {name:?} work").into();
+            let Some(fluid) = raw.fluid.get(&generator.fluid_box.filter) else {
+                log::warn!(
+                    "Skipping {name:?}, it uses unknown fluid {:?}",
+                    generator.fluid_box.filter
+                );
+                continue;
+            };
             data.machines.insert(
                 name.clone(),
                 Machine {
                     name: name.clone(),
+                    kind: MachineKind::Other,
+                    modules: MachineModules::default(),
                     categories: HashSet::from_iter([Category::Generator(name.clone())]),
                     energy_usage: HashMap::new(),
                     crafting_speed: 1.into(),
                 },
             );
 
-            let fluid_name = generator.fluid_box.filter.clone();
-            let fluid = &raw.fluid[&fluid_name];
+            let minimum_temperature = generator
+                .fluid_box
+                .minimum_temperature
+                .map_or(fluid.default_temperature, |t| Number::new(t.value()));
+            // one recipe for every temperature boilers heat the fluid to
+            let temperatures: HashSet<Number<Temperature>> = raw
+                .boiler
+                .values()
+                .filter(|boiler| boiler.output_fluid_box.filter == fluid.name)
+                .map(|boiler| boiler.target_temperature)
+                .filter(|&temperature| {
+                    temperature >= minimum_temperature && temperature > fluid.default_temperature
+                })
+                .collect();
+            for temperature in temperatures {
+                let recipe_name: Arc<str> =
+                    format!("generator {name:?} work at {temperature:?}").into();
+                // hotter fluid is accepted, but only gives energy up to the maximum temperature
+                // https://wiki.factorio.com/Prototype/Generator#fluid_usage_per_tick
+                let usable_temperature = std::cmp::min(
+                    std::cmp::min(temperature, generator.maximum_temperature),
+                    fluid.max_temperature.unwrap_or(Number::new(1e9)),
+                );
+                data.recipes.insert(
+                    recipe_name.clone(),
+                    Recipe {
+                        name: recipe_name,
+                        category: Category::Generator(name.clone()),
+                        ingredients: HashMap::from_iter([(
+                            Item::fluid(fluid, temperature),
+                            generator.fluid_usage_per_tick,
+                        )]),
+                        results: HashMap::from_iter([(
+                            Item::Energy {
+                                fuel_categories: None,
+                                energy_type: EnergyType::Electric,
+                            },
+                            Number::new((usable_temperature - fluid.default_temperature).value())
+                                * generator.fluid_usage_per_tick
+                                * Number::new(fluid.heat_capacity.value())
+                                * generator.effectivity,
+                        )]),
+                        allow_productivity: false,
+                        catalysts: HashMap::new(),
+                        crafting_time: Some(Number::new(1.0) / UPS), // 1 tick
+                    },
+                );
+            }
+        }
 
+        for reactor in raw.reactor.values() {
+            let name = reactor.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &reactor.name,
+                &reactor.energy_source,
+                reactor.consumption,
+            ) else {
+                continue;
+            };
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name: name.clone(),
+                    kind: MachineKind::Reactor {
+                        neighbour_bonus: reactor.neighbour_bonus,
+                    },
+                    modules: MachineModules::default(),
+                    categories: HashSet::from_iter([Category::Generator(name.clone())]),
+                    energy_usage,
+                    crafting_speed: 1.into(),
+                },
+            );
+            let recipe_name: Arc<str> = format!("generator {name:?} work").into();
             data.recipes.insert(
                 recipe_name.clone(),
                 Recipe {
                     name: recipe_name,
                     category: Category::Generator(name.clone()),
-                    ingredients: HashMap::from_iter([(
-                        Item::Item {
-                            name: fluid.name.arc(),
+                    ingredients: HashMap::new(),
+                    results: HashMap::from_iter([(
+                        Item::Energy {
+                            fuel_categories: None,
+                            energy_type: EnergyType::Heat,
                         },
-                        generator.fluid_usage_per_tick,
+                        Number::new(reactor.consumption.value()),
                     )]),
+                    allow_productivity: false,
+                    catalysts: HashMap::new(),
+                    crafting_time: Some(1.into()),
+                },
+            );
+        }
+
+        for pump in raw.offshore_pump.values() {
+            let name = pump.name.arc();
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name: name.clone(),
+                    kind: MachineKind::Other,
+                    modules: MachineModules::default(),
+                    categories: HashSet::from_iter([Category::OffshorePump(name.clone())]),
+                    energy_usage: HashMap::new(),
+                    crafting_speed: 1.into(),
+                },
+            );
+            // 2.0 pumps take whatever fluid the tile has, only tiles found on the planet count
+            let mut fluids: HashSet<Arc<str>> = match &pump.fluid {
+                Some(fluid) => HashSet::from_iter([fluid.arc()]),
+                None => raw
+                    .autoplaced_tiles(PLANET)
+                    .filter_map(|tile| tile.fluid.as_ref().map(|fluid| fluid.arc()))
+                    .collect(),
+            };
+            if fluids.is_empty() {
+                log::debug!("No fluid tiles on {PLANET}, {name:?} pumps water");
+                fluids.insert("water".into());
+            }
+            for fluid in fluids {
+                let recipe_name: Arc<str> = format!("pumping {fluid:?} with {name:?}").into();
+                data.recipes.insert(
+                    recipe_name.clone(),
+                    Recipe {
+                        name: recipe_name,
+                        category: Category::OffshorePump(name.clone()),
+                        ingredients: HashMap::new(),
+                        results: HashMap::from_iter([(
+                            Item::Item { name: fluid },
+                            pump.pumping_speed,
+                        )]),
+                        allow_productivity: false,
+                        catalysts: HashMap::new(),
+                        crafting_time: Some(Number::new(1.0) / UPS), // 1 tick
+                    },
+                );
+            }
+        }
+
+        for generator in raw.burner_generator.values() {
+            let name = generator.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &generator.name,
+                &generator.burner,
+                generator.max_power_output,
+            ) else {
+                continue;
+            };
+            let output = Number::new(generator.max_power_output.value());
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name: name.clone(),
+                    kind: MachineKind::Other,
+                    modules: MachineModules::default(),
+                    categories: HashSet::from_iter([Category::Generator(name.clone())]),
+                    energy_usage,
+                    crafting_speed: 1.into(),
+                },
+            );
+            let recipe_name: Arc<str> = format!("generator {name:?} work").into();
+            data.recipes.insert(
+                recipe_name.clone(),
+                Recipe {
+                    name: recipe_name,
+                    category: Category::Generator(name.clone()),
+                    ingredients: HashMap::new(),
                     results: HashMap::from_iter([(
                         Item::Energy {
-                            fuel_category: None,
+                            fuel_categories: None,
                             energy_type: EnergyType::Electric,
                         },
-                        {
-                            // https://wiki.factorio.com/Prototype/Generator#fluid_usage_per_tick
-                            Number::new(
-                                (std::cmp::min(
-                                    generator.maximum_temperature,
-                                    fluid.max_temperature.unwrap_or(Number::new(1e9)),
-                                ) - fluid.default_temperature)
-                                    .value(),
-                            ) * generator.fluid_usage_per_tick
-                                * Number::new(fluid.heat_capacity.unwrap().value())
-                                * generator.effectivity
+                        output,
+                    )]),
+                    allow_productivity: false,
+                    catalysts: HashMap::new(),
+                    crafting_time: Some(1.into()),
+                },
+            );
+        }
+
+        for solar_panel in raw.solar_panel.values() {
+            let name = solar_panel.name.arc();
+            let production = Number::new(solar_panel.production.value());
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name: name.clone(),
+                    kind: MachineKind::SolarPanel { production },
+                    modules: MachineModules::default(),
+                    categories: HashSet::from_iter([Category::Generator(name.clone())]),
+                    energy_usage: HashMap::new(),
+                    crafting_speed: 1.into(),
+                },
+            );
+            let recipe_name: Arc<str> = format!("generator {name:?} work").into();
+            data.recipes.insert(
+                recipe_name.clone(),
+                Recipe {
+                    name: recipe_name,
+                    category: Category::Generator(name.clone()),
+                    ingredients: HashMap::new(),
+                    results: HashMap::from_iter([(
+                        Item::Energy {
+                            fuel_categories: None,
+                            energy_type: EnergyType::Electric,
                         },
+                        production,
                     )]),
-                    crafting_time: Some(Number::new(1.0) / UPS), // 1 tick
+                    allow_productivity: false,
+                    catalysts: HashMap::new(),
+                    crafting_time: Some(1.into()),
+                },
+            );
+        }
+
+        for accumulator in raw.accumulator.values() {
+            let name = accumulator.name.arc();
+            let Some(buffer_capacity) = accumulator.energy_source.buffer_capacity else {
+                log::warn!("Skipping {name:?}, it has no buffer capacity");
+                continue;
+            };
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name,
+                    kind: MachineKind::Accumulator {
+                        buffer_capacity: Number::new(buffer_capacity.value()),
+                    },
+                    modules: MachineModules::default(),
+                    categories: HashSet::new(),
+                    energy_usage: HashMap::new(),
+                    crafting_speed: 1.into(),
                 },
             );
         }
 
         for boiler in raw.boiler.values() {
             let name = boiler.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &boiler.name,
+                &boiler.energy_source,
+                boiler.energy_consumption,
+            ) else {
+                continue;
+            };
+            let fluid = |filter| {
+                let fluid = raw.fluid.get(filter);
+                if fluid.is_none() {
+                    log::warn!("Skipping {name:?}, it uses unknown fluid {filter:?}");
+                }
+                fluid
+            };
+            let (Some(input), Some(output)) = (
+                fluid(&boiler.fluid_box.filter),
+                fluid(&boiler.output_fluid_box.filter),
+            ) else {
+                continue;
+            };
+            // energy needed to heat one unit from the input temperature
+            let unit_energy = Number::new(
+                (boiler.target_temperature - input.default_temperature).value()
+                    * output.heat_capacity.value(),
+            );
+            if unit_energy.value() <= 0.0 {
+                log::warn!(
+                    "Skipping {name:?}, heating {:?} to {:?} takes no energy",
+                    input.name,
+                    boiler.target_temperature,
+                );
+                continue;
+            }
             data.machines.insert(
                 name.clone(),
                 Machine {
                     name: name.clone(),
+                    kind: MachineKind::Other,
+                    modules: MachineModules::default(),
                     categories: HashSet::from_iter([Category::Boiler(name.clone())]),
-                    energy_usage: energy_ingredients(
-                        &boiler.energy_source,
-                        boiler.energy_consumption,
-                    ),
+                    energy_usage,
                     crafting_speed: 1.into(),
                 },
             );
             let recipe_name: Arc<str> = format!("boiling in {name:?}").into();
+            let amount = Number::new(boiler.energy_consumption.value()) / UPS / unit_energy;
             data.recipes.insert(
                 recipe_name.clone(),
                 Recipe {
                     name: recipe_name,
                     category: Category::Boiler(name.clone()),
                     ingredients: HashMap::from_iter([(
-                        Item::Item {
-                            name: boiler.fluid_box.filter.arc(),
-                        },
-                        1.into(),
+                        Item::fluid(input, input.default_temperature),
+                        amount,
                     )]),
                     results: HashMap::from_iter([(
-                        Item::Item {
-                            name: boiler.output_fluid_box.filter.arc(),
-                        },
-                        1.into(),
+                        Item::fluid(output, boiler.target_temperature),
+                        amount,
                     )]),
-                    crafting_time: Some(Number::new(1.0) / UPS), // TODO check if there is configuration,
+                    allow_productivity: false,
+                    catalysts: HashMap::new(),
+                    crafting_time: Some(Number::new(1.0) / UPS), // 1 tick
                 },
             );
         }
 
         for assembler in raw.assembling_machine.values() {
             let name = assembler.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &assembler.name,
+                &assembler.energy_source,
+                assembler.energy_usage,
+            ) else {
+                continue;
+            };
+            if !raw.allowed_on(PLANET, &assembler.surface_conditions) {
+                log::debug!("Skipping machine {name:?}, it can not be placed on {PLANET}");
+                continue;
+            }
             data.machines.insert(
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::Other,
+                    modules: MachineModules::from_raw(&assembler.modules),
+                    categories: assembler
+                        .crafting_categories
+                        .iter()
+                        .map(|name| Category::Craft(name.arc()))
+                        .collect(),
+                    crafting_speed: assembler.crafting_speed,
+                    energy_usage,
+                },
+            );
+        }
+
+        for silo in raw.rocket_silo.values() {
+            let assembler = &silo.machine;
+            let name = assembler.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &assembler.name,
+                &assembler.energy_source,
+                assembler.energy_usage,
+            ) else {
+                continue;
+            };
+            if !raw.allowed_on(PLANET, &assembler.surface_conditions) {
+                log::debug!("Skipping rocket silo {name:?}, it can not be placed on {PLANET}");
+                continue;
+            }
+            data.machines.insert(
+                name.clone(),
+                Machine {
+                    name: name.clone(),
+                    kind: MachineKind::Other,
+                    modules: MachineModules::from_raw(&assembler.modules),
                     categories: assembler
                         .crafting_categories
                         .iter()
                         .map(|name| Category::Craft(name.arc()))
+                        .chain([Category::RocketLaunch(name.clone())])
                         .collect(),
                     crafting_speed: assembler.crafting_speed,
-                    energy_usage: energy_ingredients(
-                        &assembler.energy_source,
-                        assembler.energy_usage,
+                    energy_usage,
+                },
+            );
+            let recipe_name: Arc<str> = format!("launching rocket in {name:?}").into();
+            data.recipes.insert(
+                recipe_name.clone(),
+                Recipe {
+                    name: recipe_name,
+                    category: Category::RocketLaunch(name.clone()),
+                    ingredients: HashMap::from_iter([(
+                        "rocket-part".into(),
+                        silo.rocket_parts_required,
+                    )]),
+                    results: HashMap::from_iter([(ROCKET_LAUNCH.into(), 1.into())]),
+                    allow_productivity: false,
+                    catalysts: HashMap::new(),
+                    crafting_time: Some(
+                        (silo.launch_wait_time + Number::new(ROCKET_FLIGHT_TICKS)) / UPS,
                     ),
                 },
             );
         }
 
+        for module in raw.module.values() {
+            let name = module.name.arc();
+            data.modules.insert(
+                name.clone(),
+                Module {
+                    name,
+                    effects: ModuleEffects::from_raw(&module.effect),
+                    limitation: (!module.limitation.is_empty()).then(|| {
+                        module
+                            .limitation
+                            .iter()
+                            .map(|recipe| recipe.arc())
+                            .collect()
+                    }),
+                },
+            );
+        }
+
+        for beacon in raw.beacon.values() {
+            let name = beacon.name.arc();
+            let Some(energy_usage) = energy_ingredients(
+                &raw,
+                &beacon.name,
+                &beacon.energy_source,
+                beacon.energy_usage,
+            ) else {
+                continue;
+            };
+            data.beacons.insert(
+                name.clone(),
+                Beacon {
+                    name,
+                    module_slots: beacon.modules.slots(),
+                    distribution_effectivity: beacon.distribution_effectivity,
+                    energy_usage,
+                    allowed_effects: beacon
+                        .allowed_effects
+                        .as_ref()
+                        .map(|effects| effects.iter().map(|effect| effect.arc()).collect()),
+                },
+            );
+        }
+
         // TODO: maybe merge character mining & crafting into 1 machine?
         {
             let name: Arc<str> = CHARACTER_MINING.into();
@@ -392,6 +1132,8 @@ impl Data {
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::CharacterMining,
+                    modules: MachineModules::default(),
                     categories: raw
                         .character
                         .mining_categories
@@ -411,6 +1153,8 @@ impl Data {
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::CharacterCrafting,
+                    modules: MachineModules::default(),
                     categories: raw
                         .character
                         .crafting_categories
@@ -423,54 +1167,216 @@ impl Data {
             );
         }
 
+        /// What the 2.0 research trigger needs to be done
+        fn trigger_ingredients(
+            raw: &crate::raw_data::Data,
+            technology: &str,
+            trigger: &ResearchTrigger,
+        ) -> HashMap<Item, Number> {
+            match trigger {
+                ResearchTrigger::CraftItem { item, count } => {
+                    HashMap::from_iter([(Item::Item { name: item.arc() }, *count)])
+                }
+                ResearchTrigger::CraftFluid { fluid, amount } => {
+                    HashMap::from_iter([(Item::Item { name: fluid.arc() }, *amount)])
+                }
+                // entities are built from the item of the same name
+                ResearchTrigger::BuildEntity { entity } => {
+                    HashMap::from_iter([(Item::Item { name: entity.arc() }, 1.into())])
+                }
+                ResearchTrigger::MineEntity { entity } => match raw.resource.get(entity) {
+                    Some(resource) => {
+                        sum_amounts(&resource.minable.results, |result| result.amount)
+                    }
+                    None => {
+                        log::debug!("{technology:?} needs mining {entity:?}, researching for free");
+                        HashMap::new()
+                    }
+                },
+                ResearchTrigger::Other => {
+                    log::debug!(
+                        "{technology:?} has an unknown research trigger, researching for free"
+                    );
+                    HashMap::new()
+                }
+            }
+        }
+
         for technology in raw.technology.values() {
             let name = technology.name.arc();
             let recipe_name: Arc<str> = format!("research {name:?}").into();
+            let (base_name, level) = split_level(&name);
+            let level = level.unwrap_or(1);
+            let (count, unit_ingredients, unit_time) =
+                match (&technology.unit, &technology.research_trigger) {
+                    (Some(unit), _) => (
+                        match &unit.count {
+                            TechnologyCount::Const { count } => ResearchCount::Const(*count),
+                            TechnologyCount::Formula { count_formula } => {
+                                match count_formula.parse() {
+                                    Ok(formula) => ResearchCount::Formula(formula),
+                                    Err(e) => {
+                                        log::warn!("Skipping technology {name:?}: {e:#}");
+                                        continue;
+                                    }
+                                }
+                            }
+                        },
+                        sum_amounts(&unit.ingredients, |ingredient| ingredient.amount),
+                        Some(Number::new(unit.time.value())),
+                    ),
+                    // the trigger items get used up, which is a bit pessimistic
+                    (None, Some(trigger)) => (
+                        ResearchCount::Const(1.into()),
+                        trigger_ingredients(&raw, &name, trigger),
+                        None,
+                    ),
+                    (None, None) => {
+                        log::warn!(
+                            "Skipping technology {name:?}, it has neither unit nor research_trigger"
+                        );
+                        continue;
+                    }
+                };
+            let mut effects = Vec::new();
+            for effect in &technology.effects {
+                match *effect {
+                    Modifier::UnlockRecipe { ref recipe } => {
+                        if let Some(technologies) = data.locked_recipes.get_mut(&recipe.arc()) {
+                            technologies.push(name.clone());
+                        }
+                    }
+                    Modifier::MiningDrillProductivityBonus { modifier } => {
+                        effects.push(Effect::MiningDrillProductivity(modifier));
+                    }
+                    Modifier::LaboratorySpeed { modifier } => {
+                        effects.push(Effect::LaboratorySpeed(modifier));
+                    }
+                    Modifier::LaboratoryProductivity { modifier } => {
+                        effects.push(Effect::LaboratoryProductivity(modifier));
+                    }
+                    Modifier::CharacterMiningSpeed { modifier } => {
+                        effects.push(Effect::CharacterMiningSpeed(modifier));
+                    }
+                    Modifier::CharacterCraftingSpeed { modifier } => {
+                        effects.push(Effect::CharacterCraftingSpeed(modifier));
+                    }
+                    Modifier::Other => {}
+                }
+            }
             data.researches.insert(
                 name.clone(),
                 Research {
-                    name,
+                    base_name: base_name.into(),
+                    name: name.clone(),
                     dependencies: technology
                         .prerequisites
                         .iter()
                         .map(|name| name.arc())
                         .collect(),
-                    recipe: recipe_name.clone(),
+                    recipe: recipe_name,
+                    level,
+                    max_level: match technology.max_level {
+                        None => Some(level),
+                        Some(MaxLevel::Level(max_level)) => Some(max_level),
+                        Some(MaxLevel::Infinite) => None,
+                    },
+                    effects,
+                    count,
+                    unit_ingredients,
+                    unit_time,
+                    ignore_tech_cost_multiplier: technology.ignore_tech_cost_multiplier
+                        || unit_time.is_none(),
                 },
             );
-            let count = match technology.unit.count {
-                crate::raw_data::TechnologyCount::Const { count } => count,
-                crate::raw_data::TechnologyCount::Formula { .. } => {
-                    // TODO
-                    continue;
-                }
-            } * if technology.ignore_tech_cost_multiplier {
-                Number::new(1.0)
+        }
+        data.set_science_multiplier(science_multiplier);
+
+        // items and fluids with a fuel value, fluids without one are not fuel
+        let fuels: Vec<Fuel> = raw
+            .item
+            .values()
+            .filter_map(|item| {
+                let fuel = item.fuel.as_ref()?;
+                Some(Fuel {
+                    item: item.name.arc(),
+                    category: fuel.category.clone(),
+                    value: Number::new(fuel.value.value()),
+                    burnt_result: fuel.burnt_result.as_ref().map(|name| name.arc()),
+                })
+            })
+            .chain(raw.fluid.values().filter_map(|fluid| {
+                let value = fluid.fuel_value.filter(|value| value.value() > 0.0)?;
+                Some(Fuel {
+                    item: fluid.name.arc(),
+                    category: FuelCategory::fluid(),
+                    value: Number::new(value.value()),
+                    burnt_result: None,
+                })
+            }))
+            .collect();
+        let mut fuel_category_sets: HashSet<(Arc<[FuelCategory]>, EnergyType)> = data
+            .machines
+            .values()
+            .flat_map(|machine| machine.energy_usage.keys())
+            .filter_map(|item| match item {
+                Item::Energy {
+                    fuel_categories: Some(categories),
+                    energy_type,
+                } => Some((categories.clone(), *energy_type)),
+                _ => None,
+            })
+            .collect();
+        for fuel in &fuels {
+            let energy_type = if fuel.category == FuelCategory::fluid() {
+                EnergyType::Fluid
             } else {
-                science_multiplier
+                EnergyType::Burner
             };
-            data.recipes.insert(
-                recipe_name.clone(),
-                Recipe {
-                    name: recipe_name,
-                    category: Category::Research,
-                    ingredients: technology
-                        .unit
-                        .ingredients
-                        .iter()
-                        .map(|ingredient| {
-                            (
-                                Item::Item {
-                                    name: ingredient.name.arc(),
+            fuel_category_sets.insert((Arc::from([fuel.category.clone()]), energy_type));
+        }
+        // every fuel can be burnt in every machine accepting its category
+        for (categories, energy_type) in &fuel_category_sets {
+            for fuel in fuels
+                .iter()
+                .filter(|fuel| categories.contains(&fuel.category))
+            {
+                let name = burnable_fuel_energy_recipe(&fuel.item, categories);
+                data.recipes.insert(
+                    name.clone(),
+                    Recipe {
+                        category: Category::BurnableFuelEnergy(fuel.category.clone()),
+                        name,
+                        ingredients: HashMap::from_iter([(
+                            Item::Item {
+                                name: fuel.item.clone(),
+                            },
+                            1.into(),
+                        )]),
+                        results: HashMap::from_iter(
+                            [(
+                                Item::Energy {
+                                    fuel_categories: Some(categories.clone()),
+                                    energy_type: *energy_type,
                                 },
-                                ingredient.amount * count,
-                            )
-                        })
-                        .collect(),
-                    results: HashMap::new(),
-                    crafting_time: Some(Number::new(technology.unit.time.value()) * count),
-                },
-            );
+                                fuel.value,
+                            )]
+                            .into_iter()
+                            .chain(fuel.burnt_result.iter().map(|burnt_result| {
+                                (
+                                    Item::Item {
+                                        name: burnt_result.clone(),
+                                    },
+                                    1.into(),
+                                )
+                            })),
+                        ),
+                        allow_productivity: false,
+                        catalysts: HashMap::new(),
+                        crafting_time: None,
+                    },
+                );
+            }
         }
 
         {
@@ -479,12 +1385,15 @@ impl Data {
                 name.clone(),
                 Machine {
                     name,
+                    kind: MachineKind::Other,
+                    modules: MachineModules::default(),
                     categories: HashSet::from_iter([Category::Free]),
                     energy_usage: HashMap::new(),
                     crafting_speed: 1.into(),
                 },
             );
 
+            // only used if the free machine is placed, see `World::free_water`
             #[allow(clippy::single_element_loop)]
             for item in ["water"] {
                 let recipe_name: Arc<str> = item.into();
@@ -495,6 +1404,8 @@ impl Data {
                         category: Category::Free,
                         ingredients: HashMap::new(),
                         results: HashMap::from_iter([(item.into(), 1.into())]),
+                        allow_productivity: false,
+                        catalysts: HashMap::new(),
                         crafting_time: None,
                     },
                 );
@@ -504,4 +1415,178 @@ impl Data {
         log::trace!("{data:#?}");
         Ok(data)
     }
+
+    /// Regenerates every research recipe, everything else stays the same
+    pub fn set_science_multiplier(&mut self, science_multiplier: Number) {
+        self.science_multiplier = science_multiplier;
+        for research in self.researches.values() {
+            let recipe = research.research_recipe(research.level, science_multiplier);
+            self.recipes.insert(recipe.name.clone(), recipe);
+        }
+    }
+
+    /// Solar panels produce `fraction` of their peak output on average
+    pub fn set_solar_fraction(&mut self, fraction: Number) {
+        for machine in self.machines.values() {
+            if let MachineKind::SolarPanel { production } = machine.kind {
+                // panels registered with modules share the recipe of the plain panel
+                let Some(recipe) = self
+                    .recipes
+                    .get_mut(&*format!("generator {:?} work", machine.name))
+                else {
+                    continue;
+                };
+                for amount in recipe.results.values_mut() {
+                    *amount = production * fraction;
+                }
+            }
+        }
+    }
+
+    /// Registers a machine with modules as a separate machine, returns its name
+    pub fn machine_with_modules(
+        &mut self,
+        machine: &str,
+        setup: &ModuleSetup,
+    ) -> anyhow::Result<Arc<str>> {
+        let mut result = self
+            .machines
+            .get(machine)
+            .with_context(|| format!("Unknown machine {machine:?}"))?
+            .clone();
+        let mut name = machine.to_owned();
+
+        let module_count: usize = setup.modules.iter().map(|&(_, count)| count).sum();
+        if module_count > result.modules.slots {
+            anyhow::bail!(
+                "{machine:?} only has {} module slots, tried to insert {module_count}",
+                result.modules.slots,
+            );
+        }
+        if !setup.modules.is_empty() {
+            name += " with";
+        }
+        for (module, count) in &setup.modules {
+            let module = self.module(module)?;
+            result
+                .modules
+                .effects
+                .add(&module.effects, Number::from(*count as i64));
+            restrict(&mut result.modules.limitation, &module.limitation);
+            name += &format!(" {}x{count}", module.name);
+        }
+
+        if setup.beacons != 0 {
+            let beacon_name = setup.beacon.as_deref().unwrap_or("beacon");
+            let beacon = self
+                .beacons
+                .get(beacon_name)
+                .with_context(|| format!("Unknown beacon {beacon_name:?}"))?;
+            let module_count: usize = setup.beacon_modules.iter().map(|&(_, count)| count).sum();
+            if module_count > beacon.module_slots {
+                anyhow::bail!(
+                    "{beacon_name:?} only has {} module slots, tried to insert {module_count}",
+                    beacon.module_slots,
+                );
+            }
+            name += &format!(" beacons {}x{}", setup.beacons, beacon.name);
+            let beacons = Number::from(setup.beacons as i64);
+            for (module, count) in &setup.beacon_modules {
+                let module = self.module(module)?;
+                if let Some(allowed) = &beacon.allowed_effects {
+                    for (effect, value) in [
+                        ("speed", module.effects.speed),
+                        ("productivity", module.effects.productivity),
+                        ("consumption", module.effects.consumption),
+                    ] {
+                        if value.value() != 0.0 && !allowed.contains(effect) {
+                            anyhow::bail!("{:?} can not have {:?} in it", beacon.name, module.name);
+                        }
+                    }
+                }
+                result.modules.effects.add(
+                    &module.effects,
+                    beacons * beacon.distribution_effectivity * Number::from(*count as i64),
+                );
+                restrict(&mut result.modules.limitation, &module.limitation);
+                name += &format!(" {}x{count}", module.name);
+            }
+            // each machine is assumed to have its own beacons
+            for (energy_item, &usage) in &beacon.energy_usage {
+                *result
+                    .modules
+                    .beacon_energy_usage
+                    .entry(energy_item.clone())
+                    .or_default() += usage * beacons;
+            }
+        }
+
+        let name: Arc<str> = name.into();
+        result.name = name.clone();
+        self.machines.insert(name.clone(), result);
+        Ok(name)
+    }
+
+    fn module(&self, name: &str) -> anyhow::Result<&Module> {
+        self.modules
+            .get(name)
+            .with_context(|| format!("Unknown module {name:?}"))
+    }
+
+    pub fn find_research_level(&self, base_name: &str, level: usize) -> Option<&Research> {
+        self.researches
+            .values()
+            .find(|research| &*research.base_name == base_name && research.has_level(level))
+    }
+
+    /// Recipe for researching a level of an infinite research, returns recipe name
+    pub fn insert_research_recipe(&mut self, research: &str, level: usize) -> Arc<str> {
+        let recipe = self.researches[research].research_recipe(level, self.science_multiplier);
+        let name = recipe.name.clone();
+        self.recipes.insert(name.clone(), recipe);
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_separates_close_multipliers() {
+        let key = |multiplier: f64| cache_key(0, RecipeMode::Normal, Number::new(multiplier));
+        assert_ne!(key(1200.0), key(1234.0));
+        assert_ne!(key(0.45), key(0.5));
+        assert_eq!(key(0.45), key(0.45));
+    }
+
+    #[test]
+    fn cache_round_trip_keeps_the_multiplier() {
+        let dir =
+            std::env::temp_dir().join(format!("factorio-simulator-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("XDG_CACHE_HOME", &dir);
+        let dump = dir.join(crate::dump::FILE_NAME);
+        std::fs::write(&dump, "{}").unwrap();
+        for _ in 0..2 {
+            for multiplier in [1200.0, 1234.0] {
+                let data = Data::new(&dump, RecipeMode::Normal, Number::new(multiplier)).unwrap();
+                assert_eq!(data.science_multiplier.value(), multiplier);
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repeated_results_add_up() {
+        use crate::raw_data::AmountOf;
+        let results = [
+            AmountOf::new("iron-plate".into(), 2.into()),
+            AmountOf::random("iron-plate".into(), Number::new(0.5), 1.into(), 3.into()),
+            AmountOf::new("copper-plate".into(), 1.into()),
+        ];
+        let amounts = sum_amounts(&results, |result| result.amount);
+        assert_eq!(amounts[&"iron-plate".into()], 3.into());
+        assert_eq!(amounts[&"copper-plate".into()], 1.into());
+    }
 }
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fmt::Debug, path::Path, sync::Arc};
+
+use itertools::Itertools;
+
+use crate::{
+    data::{Data, Item, Machine, Recipe, Research},
+    number::Number,
+};
+
+/// Logs what `other` (like a modded dump) changed compared to `base`
+pub fn log_diff(base_path: &Path, base: &Data, other_path: &Path, other: &Data) {
+    log::info!(
+        "Comparing {} against {}:",
+        other_path.display(),
+        base_path.display()
+    );
+    log_changes("recipe", &base.recipes, &other.recipes, recipe_changes);
+    log_changes("machine", &base.machines, &other.machines, machine_changes);
+    log_changes(
+        "research",
+        &base.researches,
+        &other.researches,
+        research_changes,
+    );
+}
+
+fn log_changes<T>(
+    what: &str,
+    base: &HashMap<Arc<str>, T>,
+    other: &HashMap<Arc<str>, T>,
+    changes: impl Fn(&T, &T) -> Vec<String>,
+) {
+    for name in base
+        .keys()
+        .filter(|name| !other.contains_key(*name))
+        .sorted()
+    {
+        log::info!("  removed {what} {name:?}");
+    }
+    for name in other
+        .keys()
+        .filter(|name| !base.contains_key(*name))
+        .sorted()
+    {
+        log::info!("  added {what} {name:?}");
+    }
+    for name in base
+        .keys()
+        .filter(|name| other.contains_key(*name))
+        .sorted()
+    {
+        let changes = changes(&base[name], &other[name]);
+        if !changes.is_empty() {
+            log::info!("  changed {what} {name:?}: {}", changes.join(", "));
+        }
+    }
+}
+
+fn change<T: Debug + PartialEq>(field: &str, base: T, other: T) -> Option<String> {
+    (base != other).then(|| format!("{field} {base:?} -> {other:?}"))
+}
+
+/// Sorted, so the output does not depend on hash map order
+fn amounts(amounts: &HashMap<Item, Number>) -> Vec<String> {
+    amounts
+        .iter()
+        .map(|(item, amount)| format!("{item:?} x{amount:?}"))
+        .sorted()
+        .collect()
+}
+
+fn sorted<T: Debug>(set: impl IntoIterator<Item = T>) -> Vec<String> {
+    set.into_iter().map(|x| format!("{x:?}")).sorted().collect()
+}
+
+fn recipe_changes(base: &Recipe, other: &Recipe) -> Vec<String> {
+    [
+        change("category", &base.category, &other.category),
+        change(
+            "ingredients",
+            amounts(&base.ingredients),
+            amounts(&other.ingredients),
+        ),
+        change("results", amounts(&base.results), amounts(&other.results)),
+        change("time", base.crafting_time, other.crafting_time),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn machine_changes(base: &Machine, other: &Machine) -> Vec<String> {
+    [
+        change("speed", base.crafting_speed, other.crafting_speed),
+        change(
+            "categories",
+            sorted(&base.categories),
+            sorted(&other.categories),
+        ),
+        change(
+            "energy",
+            amounts(&base.energy_usage),
+            amounts(&other.energy_usage),
+        ),
+        change("module slots", base.modules.slots, other.modules.slots),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn research_changes(base: &Research, other: &Research) -> Vec<String> {
+    [
+        change("count", &base.count, &other.count),
+        change(
+            "unit ingredients",
+            amounts(&base.unit_ingredients),
+            amounts(&other.unit_ingredients),
+        ),
+        change("unit time", base.unit_time, other.unit_time),
+        change(
+            "dependencies",
+            sorted(&base.dependencies),
+            sorted(&other.dependencies),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub const FILE_NAME: &str = "data-raw-dump.json";
+pub const ENV_VAR: &str = "FACTORIO_DATA_DUMP";
+/// Bump when the cached data changes shape
+const CACHE_VERSION: u32 = 4;
+
+/// Where `factorio --dump-data` puts the dump, `--data` and `FACTORIO_DATA_DUMP` take priority
+pub fn find(explicit: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    if let Some(path) = explicit {
+        if !path.is_file() {
+            bail!("--data {path:?} does not exist");
+        }
+        return Ok(path);
+    }
+    if let Some(path) = std::env::var_os(ENV_VAR) {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            bail!("{ENV_VAR}={path:?} does not exist");
+        }
+        return Ok(path);
+    }
+
+    let candidates = candidates();
+    if let Some(path) = candidates.iter().find(|path| path.is_file()) {
+        return Ok(path.clone());
+    }
+    let mut message = format!("Could not find {FILE_NAME}, looked in:\n");
+    for path in &candidates {
+        message += &format!("  {}\n", path.display());
+    }
+    message += &format!(
+        "Run `factorio --dump-data`, or point to the dump with --data <path> or {ENV_VAR}"
+    );
+    bail!(message)
+}
+
+/// Current directory first, then script-output of the usual Factorio installs
+fn candidates() -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from(FILE_NAME)];
+    let script_output = |dir: &Path| dir.join("script-output").join(FILE_NAME);
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        // linux
+        candidates.push(script_output(&home.join(".factorio")));
+        // steam flatpak
+        candidates.push(script_output(
+            &home.join(".var/app/com.valvesoftware.Steam/.factorio"),
+        ));
+        // macos
+        candidates.push(script_output(
+            &home.join("Library/Application Support/factorio"),
+        ));
+    }
+    if let Some(app_data) = std::env::var_os("APPDATA").map(PathBuf::from) {
+        // windows
+        candidates.push(script_output(&app_data.join("Factorio")));
+    }
+    candidates
+}
+
+/// Hash of the dump for cache keys, read in chunks so the dump is never whole in memory
+pub fn hash(path: &Path) -> anyhow::Result<u64> {
+    use std::io::Read;
+    let mut file = std::fs::File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+    let mut hasher = xxhash_rust::xxh3::Xxh3Default::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {path:?}"))?;
+        if read == 0 {
+            return Ok(hasher.digest());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+fn cache_path(key: &str) -> PathBuf {
+    let dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join("factorio-simulator").join(format!(
+        "{}-v{CACHE_VERSION}-{key}.msgpack",
+        env!("CARGO_PKG_VERSION")
+    ))
+}
+
+/// `None` if there is no usable cache, it will be rebuilt then
+pub fn load_cached<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let path = cache_path(key);
+    let bytes = std::fs::read(&path).ok()?;
+    match rmp_serde::from_slice(&bytes) {
+        Ok(value) => {
+            log::debug!("Loaded cache {}", path.display());
+            Some(value)
+        }
+        Err(e) => {
+            log::warn!("Ignoring broken cache {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Failing to write the cache only makes the next run slower
+pub fn save_cached<T: Serialize>(key: &str, value: &T) {
+    let path = cache_path(key);
+    let result = (|| -> anyhow::Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let bytes = rmp_serde::to_vec(value)?;
+        // write to a temporary file first, concurrent runs must never see half a cache
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp, bytes).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => log::debug!("Saved cache {}", path.display()),
+        Err(e) => log::warn!("Failed to save cache {}: {e:#}", path.display()),
+    }
+}
@@ -0,0 +1,246 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::number::Number;
+
+/// Technology `count_formula`, like `2^(L-6)*1000`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Formula {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Expr {
+    Number(f64),
+    Level,
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Level,
+    Op(Op),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        tokens.push(match c {
+            c if c.is_whitespace() => continue,
+            'L' | 'l' => Token::Level,
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '/' => Token::Op(Op::Div),
+            '^' => Token::Op(Op::Pow),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                Token::Number(s[start..end].parse()?)
+            }
+            _ => bail!("Unexpected {c:?} in formula {s:?}"),
+        });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[Op],
+        operand: fn(&mut Self) -> anyhow::Result<Expr>,
+    ) -> anyhow::Result<Expr> {
+        let mut lhs = operand(self)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.next();
+            let rhs = operand(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::Add, Op::Sub], Self::product)
+    }
+
+    fn product(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::Mul, Op::Div], Self::unary)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.peek() == Some(Token::Op(Op::Sub)) {
+            self.next();
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> anyhow::Result<Expr> {
+        let base = self.atom()?;
+        if self.peek() == Some(Token::Op(Op::Pow)) {
+            self.next();
+            // right associative, 2^3^2 = 2^9
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(Op::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Level) => Ok(Expr::Level),
+            Some(Token::Open) => {
+                let expr = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    token => bail!("Expected ')', got {token:?}"),
+                }
+            }
+            token => bail!("Unexpected {token:?}"),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser
+            .sum()
+            .map_err(|e| anyhow!("Failed to parse formula {s:?}: {e}"))?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {token:?} in formula {s:?}");
+        }
+        Ok(Self { expr })
+    }
+}
+
+impl Expr {
+    fn eval(&self, level: f64) -> f64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Level => level,
+            Expr::Neg(expr) => -expr.eval(level),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(level);
+                let rhs = rhs.eval(level);
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                    Op::Pow => lhs.powf(rhs),
+                }
+            }
+        }
+    }
+}
+
+impl Formula {
+    pub fn eval(&self, level: usize) -> Number {
+        Number::new(self.expr.eval(level as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(formula: &str, level: usize) -> f64 {
+        formula.parse::<Formula>().unwrap().eval(level).value()
+    }
+
+    #[test]
+    fn vanilla_formulas() {
+        assert_eq!(eval("2^(L-6)*1000", 6), 1000.0);
+        assert_eq!(eval("2^(L-6)*1000", 8), 4000.0);
+        assert_eq!(eval("2500*(L - 3)", 4), 2500.0);
+        assert_eq!(eval("(L-6)*1000", 10), 4000.0);
+        assert_eq!(eval("1.5^(L-1)*1000", 3), 2250.0);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1+2*3", 0), 7.0);
+        assert_eq!(eval("(1+2)*3", 0), 9.0);
+        assert_eq!(eval("10-4-3", 0), 3.0);
+        assert_eq!(eval("12/2/3", 0), 2.0);
+        assert_eq!(eval("2*3^2", 0), 18.0);
+        assert_eq!(eval("2^3^2", 0), 512.0);
+        assert_eq!(eval("((2))", 0), 2.0);
+    }
+
+    #[test]
+    fn level() {
+        assert_eq!(eval("L", 7), 7.0);
+        assert_eq!(eval("l*2", 7), 14.0);
+        assert_eq!(eval("L^2", 3), 9.0);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-2", 0), -2.0);
+        assert_eq!(eval("-2^2", 0), -4.0);
+        assert_eq!(eval("2^-1", 0), 0.5);
+        assert_eq!(eval("3--2", 0), 5.0);
+        assert_eq!(eval("-(L-1)", 4), -3.0);
+    }
+
+    #[test]
+    fn malformed() {
+        for formula in ["", "2^", "(L-6", "L-6)", "2 3", "1+*2", "L$2", "1..2", "()"] {
+            assert!(
+                formula.parse::<Formula>().is_err(),
+                "{formula:?} should not parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::bail;
+
+const EPS: f64 = 1e-9;
+
+/// Minimize `cost · x` subject to `constraints[i] · x >= bounds[i]` and `x >= 0`
+#[derive(Debug, Default)]
+pub struct Problem {
+    pub cost: Vec<f64>,
+    pub constraints: Vec<Vec<f64>>,
+    pub bounds: Vec<f64>,
+}
+
+/// Dense two-phase simplex with Bland's rule,
+/// good enough for a few hundred recipes
+struct Tableau {
+    /// Last column is the right hand side
+    rows: Vec<Vec<f64>>,
+    basis: Vec<usize>,
+}
+
+impl Tableau {
+    fn rhs(&self, row: usize) -> f64 {
+        *self.rows[row].last().unwrap()
+    }
+
+    fn pivot(&mut self, row: usize, col: usize) {
+        let value = self.rows[row][col];
+        for x in &mut self.rows[row] {
+            *x /= value;
+        }
+        let pivot_row = self.rows[row].clone();
+        for (i, other) in self.rows.iter_mut().enumerate() {
+            let factor = other[col];
+            if i == row || factor == 0.0 {
+                continue;
+            }
+            for (x, &p) in other.iter_mut().zip(&pivot_row) {
+                *x -= factor * p;
+            }
+        }
+        self.basis[row] = col;
+    }
+
+    /// Minimize `cost`, only letting `allowed` columns enter the basis
+    fn optimize(&mut self, cost: &[f64], allowed: impl Fn(usize) -> bool) -> anyhow::Result<()> {
+        loop {
+            let entering = (0..cost.len()).filter(|&col| allowed(col)).find(|&col| {
+                let reduced = cost[col]
+                    - self
+                        .rows
+                        .iter()
+                        .zip(&self.basis)
+                        .map(|(row, &basic)| cost[basic] * row[col])
+                        .sum::<f64>();
+                reduced < -EPS
+            });
+            let Some(col) = entering else {
+                return Ok(());
+            };
+            let leaving = (0..self.rows.len())
+                .filter(|&row| self.rows[row][col] > EPS)
+                .min_by(|&a, &b| {
+                    let ratio = |row: usize| self.rhs(row) / self.rows[row][col];
+                    ratio(a)
+                        .partial_cmp(&ratio(b))
+                        .unwrap()
+                        .then(self.basis[a].cmp(&self.basis[b]))
+                });
+            let Some(row) = leaving else {
+                bail!("Linear program is unbounded");
+            };
+            self.pivot(row, col);
+        }
+    }
+}
+
+pub fn minimize(problem: &Problem) -> anyhow::Result<Vec<f64>> {
+    let vars = problem.cost.len();
+    let rows = problem.constraints.len();
+    // columns: variables, then surplus for every row, then artificial for every row
+    let surplus = |row: usize| vars + row;
+    let artificial = |row: usize| vars + rows + row;
+    let cols = vars + 2 * rows;
+
+    let mut tableau = Tableau {
+        rows: Vec::with_capacity(rows),
+        basis: (0..rows).map(artificial).collect(),
+    };
+    for (i, (constraint, &bound)) in problem.constraints.iter().zip(&problem.bounds).enumerate() {
+        // scale so that values of very different magnitudes (joules vs items) play nice
+        let scale = constraint
+            .iter()
+            .chain([&bound])
+            .fold(0.0f64, |max, x| max.max(x.abs()));
+        let scale = if scale > 0.0 { scale } else { 1.0 };
+        // a·x - s = b, flipped to keep the right hand side non-negative
+        let sign = if bound < 0.0 { -1.0 } else { 1.0 };
+        let mut row = vec![0.0; cols + 1];
+        for (x, &a) in row.iter_mut().zip(constraint) {
+            *x = sign * a / scale;
+        }
+        row[surplus(i)] = -sign;
+        row[artificial(i)] = 1.0;
+        row[cols] = sign * bound / scale;
+        tableau.rows.push(row);
+    }
+
+    let mut phase1_cost = vec![0.0; cols];
+    for i in 0..rows {
+        phase1_cost[artificial(i)] = 1.0;
+    }
+    tableau.optimize(&phase1_cost, |_| true)?;
+    let infeasibility: f64 = (0..rows)
+        .filter(|&row| tableau.basis[row] >= vars + rows)
+        .map(|row| tableau.rhs(row))
+        .sum();
+    if infeasibility > 1e-6 {
+        bail!("Linear program is infeasible");
+    }
+    // drive the leftover artificial variables out of the basis
+    for row in 0..rows {
+        if tableau.basis[row] < vars + rows {
+            continue;
+        }
+        if let Some(col) = (0..vars + rows).find(|&col| tableau.rows[row][col].abs() > EPS) {
+            tableau.pivot(row, col);
+        }
+    }
+
+    let mut phase2_cost = vec![0.0; cols];
+    phase2_cost[..vars].copy_from_slice(&problem.cost);
+    tableau.optimize(&phase2_cost, |col| col < vars + rows)?;
+
+    let mut solution = vec![0.0; vars];
+    for (row, &basic) in tableau.basis.iter().enumerate() {
+        if basic < vars {
+            solution[basic] = tableau.rhs(row).max(0.0);
+        }
+    }
+    Ok(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn gear_chain() {
+        // mine iron (1s), 2 iron -> 1 gear (0.5s), want 10 gears
+        let problem = Problem {
+            cost: vec![1.0, 0.5],
+            constraints: vec![vec![1.0, -2.0], vec![0.0, 1.0]],
+            bounds: vec![0.0, 10.0],
+        };
+        assert_close(&minimize(&problem).unwrap(), &[20.0, 10.0]);
+    }
+
+    #[test]
+    fn oil_cracking() {
+        // advanced oil processing: 25 heavy, 45 light, 55 petroleum, expensive because of crude
+        // heavy cracking: 40 heavy -> 30 light
+        // light cracking: 30 light -> 20 petroleum
+        // want 100 petroleum with no leftovers needed, so everything gets cracked
+        let problem = Problem {
+            cost: vec![50.0, 2.0, 2.0],
+            constraints: vec![
+                vec![25.0, -40.0, 0.0],
+                vec![45.0, 30.0, -30.0],
+                vec![55.0, 0.0, 20.0],
+            ],
+            bounds: vec![0.0, 0.0, 100.0],
+        };
+        assert_close(
+            &minimize(&problem).unwrap(),
+            &[40.0 / 39.0, 25.0 / 39.0, 85.0 / 39.0],
+        );
+    }
+
+    #[test]
+    fn infeasible() {
+        // x >= 1 and x <= 0
+        let problem = Problem {
+            cost: vec![1.0],
+            constraints: vec![vec![1.0], vec![-1.0]],
+            bounds: vec![1.0, 0.0],
+        };
+        assert!(minimize(&problem)
+            .unwrap_err()
+            .to_string()
+            .contains("infeasible"));
+    }
+
+    #[test]
+    fn unbounded() {
+        // minimize -x with x >= 1
+        let problem = Problem {
+            cost: vec![-1.0],
+            constraints: vec![vec![1.0]],
+            bounds: vec![1.0],
+        };
+        assert!(minimize(&problem)
+            .unwrap_err()
+            .to_string()
+            .contains("unbounded"));
+    }
+
+    #[test]
+    fn degenerate_does_not_cycle() {
+        // Beale's example, cycles forever with the textbook pivoting rule
+        let problem = Problem {
+            cost: vec![-0.75, 20.0, -0.5, 6.0],
+            constraints: vec![
+                vec![-0.25, 8.0, 1.0, -9.0],
+                vec![-0.5, 12.0, 0.5, -3.0],
+                vec![0.0, 0.0, -1.0, 0.0],
+            ],
+            bounds: vec![0.0, 0.0, -1.0],
+        };
+        let solution = minimize(&problem).unwrap();
+        let cost: f64 = solution.iter().zip(&problem.cost).map(|(x, c)| x * c).sum();
+        assert!((cost - -1.25).abs() < 1e-6, "{solution:?} costs {cost}");
+        assert_close(&solution, &[1.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn no_demand() {
+        let problem = Problem {
+            cost: vec![1.0, 1.0],
+            constraints: vec![vec![1.0, -1.0]],
+            bounds: vec![0.0],
+        };
+        assert_close(&minimize(&problem).unwrap(), &[0.0, 0.0]);
+    }
+}
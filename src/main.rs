@@ -1,9 +1,18 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use data::ModuleSetup;
 use number::Number;
-use raw_data::FuelCategory;
+use raw_data::{FuelCategory, Seconds};
 use smart::Tasks;
 
 mod data;
+mod diff;
+mod dump;
+mod formula;
+mod lp;
 mod number;
+mod policy;
 mod raw_data;
 mod smart;
 
@@ -17,7 +26,26 @@ fn main() -> anyhow::Result<()> {
         .parse_default_env()
         .init();
 
-    let mut world = smart::World::new(raw_data::RecipeMode::Normal, 1.into())?;
+    let mut data_paths: Vec<std::path::PathBuf> = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => data_paths.push(args.next().context("--data needs a path")?.into()),
+            _ => bail!(
+                "Unknown argument {arg:?}, usage: factorio-simulator [--data <path>]... < script"
+            ),
+        }
+    }
+    // the first dump is simulated, the others are compared against it
+    let data_path = dump::find(data_paths.first().cloned())?;
+    log::info!("Using {}", data_path.display());
+    let data = data::Data::new(&data_path, raw_data::RecipeMode::Normal, 1.into())?;
+    for other_path in data_paths.iter().skip(1) {
+        let other = data::Data::new(other_path, raw_data::RecipeMode::Normal, 1.into())?;
+        diff::log_diff(&data_path, &data, other_path, &other);
+    }
+
+    let mut world = smart::World::new(&data_path, data, 1.into());
     let mut current_tasks: Option<Tasks> = None;
 
     for line in std::io::stdin().lines() {
@@ -40,18 +68,69 @@ fn main() -> anyhow::Result<()> {
                 let tasks = current_tasks.take().expect("} after no { ???");
                 world.planner().add_tasks(tasks).think().execute(&mut world);
             }
+            "preset" => {
+                assert!(current_tasks.is_none());
+                world.apply_preset(parts.next().unwrap())?;
+            }
+            "science-multiplier" => {
+                assert!(current_tasks.is_none());
+                let multiplier: Number = parts.next().unwrap().parse().unwrap();
+                world.set_science_multiplier(multiplier);
+            }
+            "solver" => {
+                assert!(current_tasks.is_none());
+                world.solver = parts.next().unwrap().parse()?;
+            }
+            "day-cycle" => {
+                assert!(current_tasks.is_none());
+                let length: Number<Seconds> = parts.next().unwrap().parse().unwrap();
+                let mut part = || -> Number { parts.next().unwrap().parse().unwrap() };
+                world.set_day_cycle(smart::DayCycle::new(length, part(), part(), part(), part()));
+            }
+            "free-water" => {
+                assert!(current_tasks.is_none());
+                world.free_water();
+            }
+            "reactor-neighbours" => {
+                assert!(current_tasks.is_none());
+                world.reactor_neighbours = parts.next().unwrap().parse().unwrap();
+            }
             "prefer-fuel" => {
                 assert!(current_tasks.is_none());
-                let category: FuelCategory =
-                    serde_json::from_str(&format!("{:?}", parts.next().unwrap())).unwrap();
+                let category: FuelCategory = parts.next().unwrap().into();
                 let item = parts.next().unwrap();
                 world.prefer_fuel(category, item);
             }
+            "prefer-recipe" => {
+                assert!(current_tasks.is_none());
+                let item = parts.next().unwrap();
+                let recipe = parts.next().unwrap();
+                world.prefer_recipe(item, recipe);
+            }
+            "forbid-recipe" => {
+                assert!(current_tasks.is_none());
+                let pattern = parts.next().unwrap();
+                let item = parts.next();
+                world.forbid_recipe(pattern, item.map(Into::into));
+            }
+            "unforbid-recipe" => {
+                assert!(current_tasks.is_none());
+                let pattern = parts.next().unwrap();
+                world.unforbid_recipe(pattern);
+            }
             "place" => {
                 assert!(current_tasks.is_none());
                 let machine = parts.next().unwrap();
-                let amount: Number = parts.next().unwrap_or("1").parse().unwrap();
-                *world.machines.entry(machine.into()).or_default() += amount;
+                let mut parts = parts.peekable();
+                let amount: Number = match parts.peek() {
+                    Some(&amount) if amount != "with" && amount != "beacons" => {
+                        parts.next();
+                        amount.parse().unwrap()
+                    }
+                    _ => 1.into(),
+                };
+                let setup = parse_module_setup(parts).unwrap();
+                world.place(machine, amount, &setup).unwrap();
             }
             "build" => {
                 let machine = parts.next().unwrap();
@@ -62,6 +141,11 @@ fn main() -> anyhow::Result<()> {
                     world.build(machine, amount);
                 }
             }
+            "launch-rocket" => {
+                assert!(current_tasks.is_none());
+                let amount: Number = parts.next().unwrap_or("1").parse().unwrap();
+                world.launch_rocket(amount);
+            }
             "craft" => {
                 let item = parts.next().unwrap();
                 let amount: Number = parts.next().unwrap_or("1").parse().unwrap();
@@ -71,16 +155,32 @@ fn main() -> anyhow::Result<()> {
                     world.craft(item, amount);
                 }
             }
+            "give" => {
+                assert!(current_tasks.is_none());
+                let item = parts.next().unwrap();
+                let amount: Number = parts.next().unwrap_or("1").parse().unwrap();
+                world.give(item, amount);
+            }
             "research" => {
                 assert!(current_tasks.is_none());
                 let research = parts.next().unwrap();
-                world.research(research);
+                if let Some(level) = parts.next() {
+                    world.research_level(research, level.parse().unwrap());
+                } else {
+                    world.research(research);
+                }
             }
             "unresearch" => {
                 assert!(current_tasks.is_none());
                 let research = parts.next().unwrap();
                 world.unresearch(research);
             }
+            "sleep" => {
+                assert!(current_tasks.is_none());
+                let duration: Number<Seconds> = parts.next().unwrap().parse().unwrap();
+                let item = parts.next();
+                world.sleep(duration, item.map(Into::into));
+            }
             "reset-counts" => {
                 world.reset_counts();
             }
@@ -97,8 +197,66 @@ fn main() -> anyhow::Result<()> {
                     log::info!("{craft:?} = {amount:?}");
                 }
             }
+            "show-inventory" => {
+                log::info!("Inventory:");
+                let mut inventory: Vec<_> = world.inventory.iter().collect();
+                inventory.sort_by_key(|&(_, &amount)| amount);
+                for (item, amount) in inventory {
+                    log::info!("{item:?} = {amount:?}");
+                }
+            }
             _ => panic!("unknown command {command:?}"),
         }
     }
     Ok(())
 }
+
+/// `[with <module>x<n>...] [beacons <n> [<beacon>] with <module>x<n>...]`
+fn parse_module_setup<'a>(parts: impl Iterator<Item = &'a str>) -> anyhow::Result<ModuleSetup> {
+    let mut setup = ModuleSetup::default();
+    let mut parts = parts.peekable();
+    let mut in_beacon = false;
+    while let Some(part) = parts.next() {
+        match part {
+            "with" => {}
+            "beacons" => {
+                in_beacon = true;
+                setup.beacons = parts
+                    .next()
+                    .context("Expected beacon count")?
+                    .parse()
+                    .context("Invalid beacon count")?;
+                if let Some(&beacon) = parts.peek() {
+                    if beacon != "with" {
+                        setup.beacon = Some(beacon.into());
+                        parts.next();
+                    }
+                }
+            }
+            _ => {
+                let modules = if in_beacon {
+                    &mut setup.beacon_modules
+                } else {
+                    &mut setup.modules
+                };
+                for module in part.split(',').filter(|module| !module.is_empty()) {
+                    modules.push(parse_module(module)?);
+                }
+            }
+        }
+    }
+    if in_beacon && setup.beacon_modules.is_empty() {
+        bail!("Beacons need modules");
+    }
+    Ok(setup)
+}
+
+/// `speed-module-3x2` is 2 modules, `speed-module-3` is 1
+fn parse_module(s: &str) -> anyhow::Result<(Arc<str>, usize)> {
+    if let Some((name, count)) = s.rsplit_once('x') {
+        if let Ok(count) = count.parse() {
+            return Ok((name.into(), count));
+        }
+    }
+    Ok((s.into(), 1))
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
 use anyhow::anyhow;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{marker::PhantomData, str::FromStr};
 
 pub const MULTIPLIERS: [(char, f64); 3] =
@@ -47,6 +47,9 @@ enum StringOrNumber {
 pub trait NumberType {
     const PARSE_SUFFIX: bool = true;
     const SUFFIX: Option<&'static str>;
+    fn parse(value: &str) -> anyhow::Result<f64> {
+        parse(value)
+    }
     fn write(fmt: &mut std::fmt::Formatter<'_>, value: f64) -> std::fmt::Result {
         write_untyped(fmt, value)?;
         if let Some(suffix) = Self::SUFFIX {
@@ -129,7 +132,7 @@ impl<T: NumberType> TryFrom<StringOrNumber> for Number<T> {
                         .strip_suffix(suffix)
                         .ok_or(anyhow!("Value should end with {suffix:?}"))?;
                 }
-                parse(value)?
+                T::parse(value)?
             }
             StringOrNumber::Number(number) => {
                 if let Some(suffix) = T::SUFFIX.filter(|_| T::PARSE_SUFFIX) {
@@ -215,6 +218,19 @@ impl<T: NumberType> PartialEq for Number<T> {
 
 impl<T: NumberType> Eq for Number<T> {}
 
+/// Always a plain number, suffixes are only for reading the data dump
+impl<T: NumberType> Serialize for Number<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.value)
+    }
+}
+
+impl<T: NumberType> std::hash::Hash for Number<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value.to_bits().hash(state);
+    }
+}
+
 impl<T: NumberType> PartialOrd for Number<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -0,0 +1,128 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::data::Item;
+
+/// Which recipes are used for crafting items
+#[derive(Debug, Clone)]
+pub struct RecipePolicy {
+    /// Only for the greedy solver, the LP solver weighs every allowed recipe
+    preferred: HashMap<Item, Arc<str>>,
+    forbidden: Vec<ForbiddenRecipe>,
+}
+
+#[derive(Debug, Clone)]
+struct ForbiddenRecipe {
+    /// `*` matches anything
+    pattern: Arc<str>,
+    /// `None` = forbidden for all items
+    item: Option<Item>,
+}
+
+impl Default for RecipePolicy {
+    fn default() -> Self {
+        let mut policy = Self {
+            preferred: HashMap::new(),
+            forbidden: Vec::new(),
+        };
+        // mine rocks for stone, but not for coal
+        policy.forbid("*pickaxe*", Some("coal".into()));
+        policy.forbid("*barrel*", None);
+        policy.forbid("coal-liquefaction", None);
+        for item in ["petroleum-gas", "light-oil", "heavy-oil"] {
+            policy.prefer(item, "advanced-oil-processing");
+        }
+        policy
+    }
+}
+
+impl RecipePolicy {
+    pub fn prefer(&mut self, item: impl Into<Item>, recipe: impl Into<Arc<str>>) {
+        self.preferred.insert(item.into(), recipe.into());
+    }
+
+    pub fn preferred(&self, item: &Item) -> Option<&Arc<str>> {
+        self.preferred.get(item)
+    }
+
+    pub fn forbid(&mut self, pattern: impl Into<Arc<str>>, item: Option<Item>) {
+        self.forbidden.push(ForbiddenRecipe {
+            pattern: pattern.into(),
+            item,
+        });
+    }
+
+    pub fn unforbid(&mut self, pattern: &str) {
+        self.forbidden
+            .retain(|forbidden| &*forbidden.pattern != pattern);
+    }
+
+    pub fn is_forbidden(&self, recipe: &str, item: &Item) -> bool {
+        self.forbidden.iter().any(|forbidden| {
+            forbidden.item.as_ref().is_none_or(|only| only == item)
+                && glob_matches(&forbidden.pattern, recipe)
+        })
+    }
+}
+
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = s.strip_prefix(parts.next().unwrap()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcards
+        return rest.is_empty();
+    };
+    for part in middle {
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[index + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_anything() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "iron-gear-wheel"));
+        assert!(glob_matches("**", "iron-gear-wheel"));
+    }
+
+    #[test]
+    fn prefix_and_suffix() {
+        assert!(glob_matches("fill-*", "fill-water-barrel"));
+        assert!(!glob_matches("fill-*", "empty-water-barrel"));
+        assert!(glob_matches("*-barrel", "fill-water-barrel"));
+        assert!(!glob_matches("*-barrel", "barrel-fill"));
+        assert!(glob_matches("*barrel*", "barrel"));
+        assert!(glob_matches("*pickaxe*", "pickaxe mine \"rock-big\""));
+        assert!(glob_matches("fill-*-barrel", "fill-water-barrel"));
+        assert!(!glob_matches("fill-*-barrel", "fill-barrel"));
+        assert!(glob_matches("a*b*c", "abc"));
+        assert!(!glob_matches("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn no_wildcard_is_exact() {
+        assert!(glob_matches("coal-liquefaction", "coal-liquefaction"));
+        assert!(!glob_matches("coal-liquefaction", "coal-liquefaction-2"));
+        assert!(!glob_matches("coal", "coal-liquefaction"));
+        assert!(glob_matches("", ""));
+        assert!(!glob_matches("", "coal"));
+    }
+
+    #[test]
+    fn forbidden_for_one_item() {
+        let policy = RecipePolicy::default();
+        let stone_rock = "pickaxe mine \"rock-big\"";
+        assert!(policy.is_forbidden(stone_rock, &"coal".into()));
+        assert!(!policy.is_forbidden(stone_rock, &"stone".into()));
+        assert!(policy.is_forbidden("fill-water-barrel", &"water-barrel".into()));
+    }
+}
@@ -6,25 +6,55 @@ use std::{
     sync::Arc,
 };
 
-use serde::Deserialize;
+use serde::{
+    de::{
+        value::Error as ValueError, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess,
+        Visitor,
+    },
+    Deserialize, Deserializer, Serialize,
+};
 
 pub const UPS: Number = Number::new(60.0);
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
-#[serde(rename_all = "kebab-case")]
-pub enum FuelCategory {
-    Chemical,
+/// `chemical`, `nuclear` or anything mods come up with
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct FuelCategory(Name);
+
+impl FuelCategory {
+    /// Every fluid with a fuel value
+    pub fn fluid() -> Self {
+        "fluid".into()
+    }
+}
+
+impl From<&str> for FuelCategory {
+    fn from(value: &str) -> Self {
+        Self(value.into())
+    }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+impl std::fmt::Debug for FuelCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum EnergyType {
     Burner,
     Electric,
     Heat,
+    Fluid,
+    /// Free energy, needs nothing
+    Void,
+    /// Added by some mod, treated as free
+    #[serde(other)]
+    Unknown,
 }
 
-#[derive(Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(from = "String")]
 pub struct Name(Arc<str>);
 
@@ -61,6 +91,14 @@ pub struct Seconds;
 impl NumberType for Seconds {
     const PARSE_SUFFIX: bool = false;
     const SUFFIX: Option<&'static str> = Some("s");
+    /// Also accepts `h:mm:ss` and `m:ss`
+    fn parse(value: &str) -> anyhow::Result<f64> {
+        let mut seconds = 0.0;
+        for part in value.split(':') {
+            seconds = seconds * 60.0 + crate::number::parse(part)?;
+        }
+        Ok(seconds)
+    }
     fn write(fmt: &mut std::fmt::Formatter<'_>, value: f64) -> std::fmt::Result {
         if value < 5.0 {
             write!(fmt, "{value:.1}s")?;
@@ -107,6 +145,8 @@ pub struct Fuel {
     pub value: Number<Joules>,
     #[serde(rename = "fuel_category")]
     pub category: FuelCategory,
+    /// Left behind after burning, like used up uranium fuel cells
+    pub burnt_result: Option<Name>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -140,11 +180,17 @@ pub struct Fluid {
     pub name: Name,
     pub default_temperature: Number<Temperature>,
     pub max_temperature: Option<Number<Temperature>>,
-    pub heat_capacity: Option<Number<Joules>>,
+    #[serde(default = "default_heat_capacity")]
+    pub heat_capacity: Number<Joules>,
+    pub fuel_value: Option<Number<Joules>>,
     pub base_color: Color,
     pub flow_color: Color,
 }
 
+fn default_heat_capacity() -> Number<Joules> {
+    Number::new(1000.0)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MinableResourceRequiredFluid {
     #[serde(rename = "required_fluid")]
@@ -177,10 +223,147 @@ fn basic_solid() -> Name {
 #[derive(Debug, Deserialize, Clone)]
 pub struct EnergySource {
     pub r#type: EnergyType,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_emissions")]
     pub emissions_per_minute: Number,
     pub effectivity: Option<Number>,
     pub fuel_category: Option<FuelCategory>,
+    pub fuel_categories: Option<Vec<FuelCategory>>,
+    pub buffer_capacity: Option<Number<Joules>>,
+    /// Fluid sources burn fluids for their fuel value instead of using their heat
+    #[serde(default)]
+    pub burns_fluid: bool,
+    pub fluid_box: Option<EnergySourceFluidBox>,
+}
+
+/// A single number in 1.1, `{ pollution = .., spores = .. }` in 2.0, only pollution is kept
+fn deserialize_emissions<'de, D>(deserializer: D) -> Result<Number, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Emissions {
+        Pollution(Number),
+        PerType(HashMap<Name, Number>),
+    }
+    Ok(match Emissions::deserialize(deserializer)? {
+        Emissions::Pollution(pollution) => pollution,
+        Emissions::PerType(emissions) => emissions
+            .get(&"pollution".into())
+            .copied()
+            .unwrap_or_default(),
+    })
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct EnergySourceFluidBox {
+    pub filter: Option<Name>,
+}
+
+impl EnergySource {
+    /// Sorted fuel categories a burner accepts, `None` if it does not burn anything
+    pub fn fuel_categories(&self) -> Option<Vec<FuelCategory>> {
+        if self.r#type == EnergyType::Fluid && self.burns_fluid {
+            return Some(vec![FuelCategory::fluid()]);
+        }
+        if self.r#type != EnergyType::Burner {
+            return None;
+        }
+        let mut categories = match (&self.fuel_categories, &self.fuel_category) {
+            (Some(categories), _) => categories.clone(),
+            (None, Some(category)) => vec![category.clone()],
+            (None, None) => vec!["chemical".into()],
+        };
+        categories.sort();
+        categories.dedup();
+        Some(categories)
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ModuleSpecification {
+    #[serde(default)]
+    pub module_slots: usize,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct EffectReceiver {
+    #[serde(default)]
+    pub base_effect: ModuleEffect,
+}
+
+/// Module slots of a machine, nested in `module_specification` in 1.1 and top level in 2.0
+#[derive(Debug, Deserialize, Default)]
+pub struct MachineModules {
+    module_specification: Option<ModuleSpecification>,
+    module_slots: Option<usize>,
+    /// Effects the machine has on its own, like the 2.0 foundry productivity
+    pub effect_receiver: Option<EffectReceiver>,
+}
+
+impl MachineModules {
+    pub fn slots(&self) -> usize {
+        self.module_slots
+            .or(self
+                .module_specification
+                .as_ref()
+                .map(|specification| specification.module_slots))
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(from = "ModuleEffectValueProxy")]
+pub struct ModuleEffectValue {
+    pub bonus: Number,
+}
+
+/// `{ bonus = 0.5 }` in 1.1, just `0.5` in 2.0
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModuleEffectValueProxy {
+    Bonus { bonus: Number },
+    Value(Number),
+}
+
+impl From<ModuleEffectValueProxy> for ModuleEffectValue {
+    fn from(value: ModuleEffectValueProxy) -> Self {
+        match value {
+            ModuleEffectValueProxy::Bonus { bonus } | ModuleEffectValueProxy::Value(bonus) => {
+                Self { bonus }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct ModuleEffect {
+    pub speed: Option<ModuleEffectValue>,
+    pub productivity: Option<ModuleEffectValue>,
+    pub consumption: Option<ModuleEffectValue>,
+    pub pollution: Option<ModuleEffectValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Module {
+    pub name: Name,
+    pub category: Name,
+    pub effect: ModuleEffect,
+    /// Recipes this module can be used for, empty = any
+    #[serde(default)]
+    pub limitation: Vec<Name>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Beacon {
+    pub name: Name,
+    pub energy_usage: Number<Watts>,
+    pub energy_source: EnergySource,
+    pub distribution_effectivity: Number,
+    #[serde(flatten)]
+    pub modules: MachineModules,
+    pub allowed_effects: Option<Vec<Name>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -191,6 +374,8 @@ pub struct MiningDrill {
     pub mining_speed: Number,
     pub energy_usage: Number<Watts>,
     pub energy_source: EnergySource,
+    #[serde(flatten)]
+    pub modules: MachineModules,
 }
 
 #[derive(Debug, Deserialize)]
@@ -206,6 +391,33 @@ pub enum TechnologyCount {
     Formula { count_formula: String },
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum MaxLevel {
+    Level(usize),
+    Infinite,
+}
+
+impl<'de> Deserialize<'de> for MaxLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Proxy {
+            Level(usize),
+            Name(String),
+        }
+        match Proxy::deserialize(deserializer)? {
+            Proxy::Level(level) => Ok(Self::Level(level)),
+            Proxy::Name(name) if name == "infinite" => Ok(Self::Infinite),
+            Proxy::Name(name) => Err(serde::de::Error::custom(format!(
+                "Expected a number or \"infinite\", got {name:?}"
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TechnologyUnit {
     #[serde(flatten)]
@@ -228,6 +440,18 @@ pub struct DifficultySettings {
     pub technology_price_multiplier: Option<Number>,
 }
 
+impl DifficultySettings {
+    pub fn recipe_mode(&self) -> anyhow::Result<Option<RecipeMode>> {
+        // defines.difficulty_settings.recipe_difficulty
+        Ok(match self.recipe_difficulty {
+            None => None,
+            Some(0) => Some(RecipeMode::Normal),
+            Some(1) => Some(RecipeMode::Expensive),
+            Some(other) => anyhow::bail!("Unknown recipe difficulty {other}"),
+        })
+    }
+}
+
 #[derive(Default, Debug, Deserialize)]
 #[serde(default)]
 pub struct MapGenPresetAdvancedSettings {
@@ -241,14 +465,72 @@ pub struct MapGenPreset {
     pub advanced_settings: MapGenPresetAdvancedSettings,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum Modifier {
+    UnlockRecipe {
+        recipe: Name,
+    },
+    MiningDrillProductivityBonus {
+        modifier: Number,
+    },
+    LaboratorySpeed {
+        modifier: Number,
+    },
+    LaboratoryProductivity {
+        modifier: Number,
+    },
+    CharacterMiningSpeed {
+        modifier: Number,
+    },
+    CharacterCraftingSpeed {
+        modifier: Number,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// 2.0 technologies researched by doing something instead of consuming science packs
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum ResearchTrigger {
+    CraftItem {
+        item: Name,
+        #[serde(default = "default_trigger_count")]
+        count: Number,
+    },
+    CraftFluid {
+        fluid: Name,
+        #[serde(default = "default_trigger_count")]
+        amount: Number,
+    },
+    BuildEntity {
+        entity: Name,
+    },
+    MineEntity {
+        entity: Name,
+    },
+    #[serde(other)]
+    Other,
+}
+
+fn default_trigger_count() -> Number {
+    1.into()
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Technology {
     pub name: Name,
-    pub unit: TechnologyUnit,
+    #[serde(default)]
+    pub effects: Vec<Modifier>,
+    /// Researched in labs, 2.0 technologies may have a `research_trigger` instead
+    pub unit: Option<TechnologyUnit>,
+    pub research_trigger: Option<ResearchTrigger>,
     #[serde(default)]
     pub prerequisites: Vec<Name>,
     #[serde(default)]
     pub ignore_tech_cost_multiplier: bool,
+    pub max_level: Option<MaxLevel>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -257,13 +539,15 @@ pub struct Lab {
     pub energy_usage: Number<Watts>,
     pub energy_source: EnergySource,
     pub researching_speed: Number,
+    #[serde(flatten)]
+    pub modules: MachineModules,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Boiler {
     pub name: Name,
     pub minable: Minable,
-    pub target_temperature: Number,
+    pub target_temperature: Number<Temperature>,
     pub fluid_box: FluidBox,
     pub output_fluid_box: FluidBox,
     pub energy_consumption: Number<Watts>,
@@ -281,6 +565,54 @@ pub struct Generator {
     pub fluid_box: FluidBox,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct OffshorePump {
+    pub name: Name,
+    pub minable: Minable,
+    /// 1.1 only, in 2.0 the fluid comes from the tile the pump is placed on
+    pub fluid: Option<Name>,
+    /// Per tick
+    pub pumping_speed: Number,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BurnerGenerator {
+    pub name: Name,
+    pub minable: Minable,
+    pub max_power_output: Number<Watts>,
+    pub burner: EnergySource,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Reactor {
+    pub name: Name,
+    pub minable: Minable,
+    pub consumption: Number<Watts>,
+    pub energy_source: EnergySource,
+    /// Extra output per active neighbouring reactor
+    #[serde(default = "default_neighbour_bonus")]
+    pub neighbour_bonus: Number,
+}
+
+fn default_neighbour_bonus() -> Number {
+    1.into()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SolarPanel {
+    pub name: Name,
+    pub minable: Minable,
+    /// Output at full daylight
+    pub production: Number<Watts>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Accumulator {
+    pub name: Name,
+    pub minable: Minable,
+    pub energy_source: EnergySource,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AssemblingMachine {
     pub name: Name,
@@ -289,12 +621,53 @@ pub struct AssemblingMachine {
     pub crafting_speed: Number,
     pub energy_usage: Number<Watts>,
     pub energy_source: EnergySource,
+    #[serde(flatten)]
+    pub modules: MachineModules,
+    #[serde(default)]
+    pub surface_conditions: Vec<SurfaceCondition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RocketSilo {
+    #[serde(flatten)]
+    pub machine: AssemblingMachine,
+    pub rocket_parts_required: Number,
+    /// Ticks between the rocket being ready and lifting off
+    #[serde(default = "default_launch_wait_time")]
+    pub launch_wait_time: Number,
+}
+
+fn default_launch_wait_time() -> Number {
+    120.into()
 }
 
 #[derive(Debug)]
 pub struct AmountOf {
     pub name: Name,
+    /// Expected value, `probability * (amount_min + amount_max) / 2`
     pub amount: Number,
+    pub probability: Number,
+    pub amount_min: Number,
+    pub amount_max: Number,
+    /// Part of the amount that productivity does not apply to
+    pub catalyst_amount: Number,
+}
+
+impl AmountOf {
+    pub fn new(name: Name, amount: Number) -> Self {
+        Self::random(name, 1.into(), amount, amount)
+    }
+
+    pub fn random(name: Name, probability: Number, amount_min: Number, amount_max: Number) -> Self {
+        Self {
+            name,
+            amount: probability * (amount_min + amount_max) / Number::from(2),
+            probability,
+            amount_min,
+            amount_max,
+            catalyst_amount: Number::new(0.0),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -308,6 +681,25 @@ pub struct Recipe {
     pub results: Vec<AmountOf>,
     #[serde(default = "default_recipe_energy")]
     pub energy_required: Number,
+    #[serde(default = "default_recipe_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub surface_conditions: Vec<SurfaceCondition>,
+    /// 2.0 only, 1.1 limits productivity through module limitations instead
+    pub allow_productivity: Option<bool>,
+}
+
+impl Recipe {
+    pub fn allow_productivity(&self, format: DumpFormat) -> bool {
+        match format {
+            DumpFormat::V1_1 => true,
+            DumpFormat::V2_0 => self.allow_productivity.unwrap_or(false),
+        }
+    }
+}
+
+fn default_recipe_enabled() -> bool {
+    true
 }
 
 fn default_recipe_category() -> Name {
@@ -318,7 +710,7 @@ fn default_recipe_energy() -> Number {
     Number::new(0.5) // TODO confirm?
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum RecipeMode {
     Normal,
@@ -374,11 +766,16 @@ where
     enum Item {
         AmountOf(Name, Number),
         TypedAmountOf {
-            r#type: Option<EntityType>,
-            probability: Option<f64>,
+            /// `item`, `fluid` or 2.0 additions like `research-progress`
+            r#type: Option<Name>,
+            probability: Option<Number>,
             name: Name,
-            #[serde(alias = "amount_min")] // TODO
-            amount: Number,
+            amount: Option<Number>,
+            amount_min: Option<Number>,
+            amount_max: Option<Number>,
+            /// Renamed to `ignored_by_productivity` in 2.0
+            #[serde(default, alias = "ignored_by_productivity")]
+            catalyst_amount: Number,
             fluidbox_index: Option<usize>,
         },
     }
@@ -393,21 +790,98 @@ where
         MaybeManyItems::Single(name) => vec![Item::AmountOf(name, Number::new(1.0))],
         MaybeManyItems::Multiple(items) => items,
     };
-    Ok(items
+    items
         .into_iter()
         .map(|item| match item {
-            Item::AmountOf(name, amount) | Item::TypedAmountOf { name, amount, .. } => {
-                AmountOf { name, amount }
+            Item::AmountOf(name, amount) => Ok(AmountOf::new(name, amount)),
+            Item::TypedAmountOf {
+                name,
+                probability,
+                amount,
+                amount_min,
+                amount_max,
+                catalyst_amount,
+                ..
+            } => {
+                let (amount_min, amount_max) = match (amount, amount_min, amount_max) {
+                    (Some(amount), _, _) => (amount, amount),
+                    (None, Some(min), Some(max)) => (min, std::cmp::max(min, max)),
+                    _ => {
+                        return Err(serde::de::Error::custom(format!(
+                            "{name:?} needs either amount or amount_min and amount_max"
+                        )))
+                    }
+                };
+                Ok(AmountOf {
+                    catalyst_amount,
+                    ..AmountOf::random(
+                        name,
+                        probability.unwrap_or(1.into()),
+                        amount_min,
+                        amount_max,
+                    )
+                })
             }
         })
-        .collect())
+        .collect()
+}
+
+/// 2.0 only, a range the surface property has to be in
+#[derive(Debug, Deserialize)]
+pub struct SurfaceCondition {
+    pub property: Name,
+    pub min: Option<Number>,
+    pub max: Option<Number>,
+}
+
+/// 2.0 only, like `pressure` or `gravity`
+#[derive(Debug, Deserialize)]
+pub struct SurfaceProperty {
+    pub name: Name,
+    pub default_value: Number,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Planet {
+    pub name: Name,
+    /// Missing properties have their default value
+    #[serde(default)]
+    pub surface_properties: HashMap<Name, Number>,
+    #[serde(default)]
+    pub map_gen_settings: PlanetMapGenSettings,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub struct PlanetMapGenSettings {
+    pub autoplace_settings: PlanetAutoplaceSettings,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub struct PlanetAutoplaceSettings {
+    pub tile: AutoplaceSettings,
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub struct AutoplaceSettings {
+    /// Only the names matter
+    pub settings: HashMap<Name, IgnoredAny>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Tile {
     pub name: Name,
     pub map_color: Color,
+    #[serde(
+        default,
+        alias = "absorptions_per_second",
+        deserialize_with = "deserialize_emissions"
+    )]
     pub pollution_absorption_per_second: Number,
+    /// 2.0 only, what offshore pumps get out of it
+    pub fluid: Option<Name>,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
@@ -537,6 +1011,7 @@ pub enum EntityType {
     ParticleSource,
     Pipe,
     PipeToGround,
+    Planet,
     PlayerDamagedAchievement,
     PlayerPort,
     PowerSwitch,
@@ -545,6 +1020,7 @@ pub enum EntityType {
     ProgrammableSpeaker,
     Projectile,
     Pump,
+    Quality,
     Radar,
     RailChainSignal,
     RailPlanner,
@@ -582,6 +1058,7 @@ pub enum EntityType {
     StorageTank,
     StraightRail,
     Stream,
+    SurfaceProperty,
     Technology,
     Tile,
     TileEffect,
@@ -609,6 +1086,41 @@ pub enum EntityType {
     WindSound,
 }
 
+impl EntityType {
+    /// Types [`Data`] keeps, everything else is skipped while parsing
+    fn is_modelled(self) -> bool {
+        matches!(
+            self,
+            Self::SimpleEntity
+                | Self::Item
+                | Self::Tile
+                | Self::Fluid
+                | Self::Recipe
+                | Self::Resource
+                | Self::MiningDrill
+                | Self::AssemblingMachine
+                | Self::Furnace
+                | Self::RocketSilo
+                | Self::Generator
+                | Self::Boiler
+                | Self::OffshorePump
+                | Self::BurnerGenerator
+                | Self::Reactor
+                | Self::SolarPanel
+                | Self::Accumulator
+                | Self::Technology
+                | Self::MapGenPresets
+                | Self::MapSettings
+                | Self::Character
+                | Self::Lab
+                | Self::Module
+                | Self::Beacon
+                | Self::Planet
+                | Self::SurfaceProperty
+        )
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MapGenPresets {
     name: Name,
@@ -629,31 +1141,19 @@ pub struct Character {
     pub crafting_categories: HashSet<Name>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "kebab-case", tag = "type")]
-pub enum Prototype {
-    SimpleEntity(SimpleEntity),
-    Item(Item),
-    Tile(Tile),
-    Fluid(Fluid),
-    Recipe(RecipeModes),
-    Resource(Resource),
-    MiningDrill(MiningDrill),
-    AssemblingMachine(AssemblingMachine),
-    Furnace(AssemblingMachine),
-    Generator(Generator),
-    Boiler(Boiler),
-    Technology(Technology),
-    MapGenPresets(MapGenPresets),
-    MapSettings(MapSettings),
-    Character(Character),
-    Lab(Lab),
-    #[serde(other)]
-    Other,
+/// Factorio version the dump comes from, the prototypes changed shape in 2.0
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    #[default]
+    V1_1,
+    /// No recipe difficulties, typed `results`, quality and surface conditions
+    V2_0,
 }
 
-#[derive(Default, Debug, Deserialize)]
+/// Deserialized type by type straight from the dump, see [`DataVisitor`]
+#[derive(Default, Debug)]
 pub struct Data {
+    pub format: DumpFormat,
     pub simple_entity: HashMap<Name, SimpleEntity>,
     pub item: HashMap<Name, Item>,
     pub tile: HashMap<Name, Tile>,
@@ -662,77 +1162,260 @@ pub struct Data {
     pub resource: HashMap<Name, Resource>,
     pub mining_drill: HashMap<Name, MiningDrill>,
     pub assembling_machine: HashMap<Name, AssemblingMachine>,
+    pub rocket_silo: HashMap<Name, RocketSilo>,
     pub generator: HashMap<Name, Generator>,
     pub boiler: HashMap<Name, Boiler>,
+    pub offshore_pump: HashMap<Name, OffshorePump>,
+    pub burner_generator: HashMap<Name, BurnerGenerator>,
+    pub reactor: HashMap<Name, Reactor>,
+    pub solar_panel: HashMap<Name, SolarPanel>,
+    pub accumulator: HashMap<Name, Accumulator>,
     pub lab: HashMap<Name, Lab>,
+    pub module: HashMap<Name, Module>,
+    pub beacon: HashMap<Name, Beacon>,
     pub technology: HashMap<Name, Technology>,
+    pub planet: HashMap<Name, Planet>,
+    pub surface_property: HashMap<Name, SurfaceProperty>,
     pub map_gen_presets: HashMap<Name, MapGenPreset>,
     pub map_settings: MapSettings,
     pub character: Character,
-    pub other: HashMap<EntityType, HashSet<Name>>,
 }
 
 impl Data {
-    pub fn from_reader(reader: impl std::io::Read) -> anyhow::Result<Self> {
-        let raw: HashMap<EntityType, HashMap<Name, Prototype>> = serde_json::from_reader(reader)?;
-        let mut data = Self::default();
-        for (entity_type, prototypes) in raw {
-            for (name, prototype) in prototypes {
-                match prototype {
-                    Prototype::SimpleEntity(simple_entity) => {
-                        data.simple_entity.insert(name, simple_entity);
-                    }
-                    Prototype::Item(item) => {
-                        data.item.insert(name, item);
-                    }
-                    Prototype::Tile(tile) => {
-                        data.tile.insert(name, tile);
-                    }
-                    Prototype::Fluid(fluid) => {
-                        data.fluid.insert(name, fluid);
-                    }
-                    Prototype::Recipe(recipe) => {
-                        data.recipe.insert(name, recipe);
-                    }
-                    Prototype::Resource(resource) => {
-                        data.resource.insert(name, resource);
-                    }
-                    Prototype::MiningDrill(mining_drill) => {
-                        data.mining_drill.insert(name, mining_drill);
-                    }
-                    Prototype::AssemblingMachine(assembling_machine) => {
-                        data.assembling_machine.insert(name, assembling_machine);
-                    }
-                    Prototype::Furnace(furnace) => {
-                        data.assembling_machine.insert(name, furnace);
-                    }
-                    Prototype::Generator(generator) => {
-                        data.generator.insert(name, generator);
-                    }
-                    Prototype::Boiler(boiler) => {
-                        data.boiler.insert(name, boiler);
-                    }
-                    Prototype::Technology(technology) => {
-                        data.technology.insert(name, technology);
-                    }
-                    Prototype::MapGenPresets(map_gen_presets) => {
-                        data.map_gen_presets = map_gen_presets.presets;
-                    }
-                    Prototype::MapSettings(map_settings) => {
-                        data.map_settings = map_settings;
-                    }
-                    Prototype::Character(character) => {
-                        data.character = character;
-                    }
-                    Prototype::Lab(lab) => {
-                        data.lab.insert(name, lab);
-                    }
-                    Prototype::Other => {
-                        data.other.entry(entity_type).or_default().insert(name);
+    pub fn from_reader(dump: impl std::io::Read) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(dump)?)
+    }
+
+    /// Whether the surface conditions hold on `planet`, always true for 1.1 dumps
+    pub fn allowed_on(&self, planet: &str, conditions: &[SurfaceCondition]) -> bool {
+        let planet = self.planet.get(&planet.into());
+        conditions.iter().all(|condition| {
+            let Some(value) = planet
+                .and_then(|planet| planet.surface_properties.get(&condition.property))
+                .or_else(|| {
+                    self.surface_property
+                        .get(&condition.property)
+                        .map(|property| &property.default_value)
+                })
+            else {
+                return true;
+            };
+            condition.min.is_none_or(|min| min <= *value)
+                && condition.max.is_none_or(|max| *value <= max)
+        })
+    }
+
+    /// Tiles the map generator places on `planet`, empty for 1.1 dumps
+    pub fn autoplaced_tiles(&self, planet: &str) -> impl Iterator<Item = &Tile> {
+        let settings = self
+            .planet
+            .get(&planet.into())
+            .map(|planet| &planet.map_gen_settings.autoplace_settings.tile.settings);
+        self.tile
+            .values()
+            .filter(move |tile| settings.is_some_and(|settings| settings.contains_key(&tile.name)))
+    }
+
+    /// Deserializes the next value of `prototypes` into the map for `entity_type`.
+    /// Prototypes we fail to understand are skipped with a warning,
+    /// so one odd modded prototype does not break the whole dump
+    fn insert<'de, A: MapAccess<'de>>(
+        &mut self,
+        entity_type: EntityType,
+        name: Name,
+        prototypes: &mut A,
+    ) -> Result<(), A::Error> {
+        macro_rules! parse {
+            () => {{
+                let raw = prototypes.next_value::<Box<serde_json::value::RawValue>>()?;
+                match serde_json::from_str(raw.get()) {
+                    Ok(prototype) => prototype,
+                    Err(e) => {
+                        log::warn!("Skipping {entity_type:?} {name:?}: {e}");
+                        return Ok(());
                     }
                 }
+            }};
+        }
+        match entity_type {
+            EntityType::SimpleEntity => {
+                let prototype = parse!();
+                self.simple_entity.insert(name, prototype);
+            }
+            EntityType::Item => {
+                let prototype = parse!();
+                self.item.insert(name, prototype);
+            }
+            EntityType::Tile => {
+                let prototype = parse!();
+                self.tile.insert(name, prototype);
+            }
+            EntityType::Fluid => {
+                let prototype = parse!();
+                self.fluid.insert(name, prototype);
+            }
+            EntityType::Recipe => {
+                let prototype = parse!();
+                self.recipe.insert(name, prototype);
+            }
+            EntityType::Resource => {
+                let prototype = parse!();
+                self.resource.insert(name, prototype);
+            }
+            EntityType::MiningDrill => {
+                let prototype = parse!();
+                self.mining_drill.insert(name, prototype);
+            }
+            EntityType::AssemblingMachine | EntityType::Furnace => {
+                let prototype = parse!();
+                self.assembling_machine.insert(name, prototype);
+            }
+            EntityType::RocketSilo => {
+                let prototype = parse!();
+                self.rocket_silo.insert(name, prototype);
+            }
+            EntityType::Generator => {
+                let prototype = parse!();
+                self.generator.insert(name, prototype);
+            }
+            EntityType::Boiler => {
+                let prototype = parse!();
+                self.boiler.insert(name, prototype);
+            }
+            EntityType::OffshorePump => {
+                let prototype = parse!();
+                self.offshore_pump.insert(name, prototype);
+            }
+            EntityType::BurnerGenerator => {
+                let prototype = parse!();
+                self.burner_generator.insert(name, prototype);
+            }
+            EntityType::Reactor => {
+                let prototype = parse!();
+                self.reactor.insert(name, prototype);
+            }
+            EntityType::SolarPanel => {
+                let prototype = parse!();
+                self.solar_panel.insert(name, prototype);
+            }
+            EntityType::Accumulator => {
+                let prototype = parse!();
+                self.accumulator.insert(name, prototype);
+            }
+            EntityType::Technology => {
+                let prototype = parse!();
+                self.technology.insert(name, prototype);
+            }
+            EntityType::MapGenPresets => {
+                self.map_gen_presets = {
+                    let presets: MapGenPresets = parse!();
+                    presets.presets
+                };
+            }
+            EntityType::MapSettings => {
+                self.map_settings = parse!();
+            }
+            EntityType::Character => {
+                self.character = parse!();
             }
+            EntityType::Lab => {
+                let prototype = parse!();
+                self.lab.insert(name, prototype);
+            }
+            EntityType::Module => {
+                let prototype = parse!();
+                self.module.insert(name, prototype);
+            }
+            EntityType::Beacon => {
+                let prototype = parse!();
+                self.beacon.insert(name, prototype);
+            }
+            EntityType::Planet => {
+                let prototype = parse!();
+                self.planet.insert(name, prototype);
+            }
+            EntityType::SurfaceProperty => {
+                let prototype = parse!();
+                self.surface_property.insert(name, prototype);
+            }
+            _ => {
+                prototypes.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for Data {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DataVisitor)
+    }
+}
+
+/// `{ entity_type: { name: prototype } }`
+struct DataVisitor;
+
+impl<'de> Visitor<'de> for DataVisitor {
+    type Value = Data;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("data raw dump")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Data, A::Error> {
+        let mut data = Data::default();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "quality" {
+                // every 2.0 dump has at least the `normal` quality
+                data.format = DumpFormat::V2_0;
+            }
+            let Ok(entity_type) = EntityType::deserialize(
+                IntoDeserializer::<ValueError>::into_deserializer(key.as_str()),
+            ) else {
+                log::debug!("Skipping unknown prototype type {key:?}");
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+            if !entity_type.is_modelled() {
+                // skipped whole, without reading the names
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            map.next_value_seed(PrototypesSeed {
+                entity_type,
+                data: &mut data,
+            })?;
         }
+        log::debug!("Dump format {:?}", data.format);
         Ok(data)
     }
 }
+
+/// `{ name: prototype }` of a single type, inserted into `data` as they come
+struct PrototypesSeed<'a> {
+    entity_type: EntityType,
+    data: &'a mut Data,
+}
+
+impl<'de> DeserializeSeed<'de> for PrototypesSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PrototypesSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "map of {:?} prototypes", self.entity_type)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<Name>()? {
+            self.data.insert(self.entity_type, name, &mut map)?;
+        }
+        Ok(())
+    }
+}
@@ -7,26 +7,132 @@ use std::{
 use itertools::Itertools;
 
 use crate::{
+    lp,
     number::Number,
-    raw_data::{EnergyType, FuelCategory, RecipeMode, Seconds},
+    policy::RecipePolicy,
+    raw_data::{EnergyType, FuelCategory, Seconds},
 };
 
 #[derive(Clone)]
 pub struct World {
     data: Arc<Data>,
+    /// Reloaded with a different recipe mode by presets
+    data_path: std::path::PathBuf,
+    /// Set by `science-multiplier`, on top of the preset's tech price multiplier
+    science_multiplier: Number,
+    technology_price_multiplier: Number,
     pub no_thinking: bool,
+    pub solver: Solver,
     researches: HashSet<Arc<str>>,
+    bonuses: Bonuses,
     preferred_fuel: HashMap<FuelCategory, Item>,
+    recipe_policy: RecipePolicy,
     pub machines: HashMap<Arc<str>, Number>,
+    pub inventory: HashMap<Item, Number>,
+    day_cycle: DayCycle,
+    /// Average number of working neighbours of every reactor
+    pub reactor_neighbours: Number,
     time: Number<Seconds>,
     pub total_crafts: HashMap<Arc<str>, Number>,
     total_machine_time: Number<Seconds>,
 }
 
+/// Accumulated research effects
+#[derive(Debug, Default, Clone)]
+struct Bonuses {
+    mining_drill_productivity: Number,
+    laboratory_speed: Number,
+    laboratory_productivity: Number,
+    character_mining_speed: Number,
+    character_crafting_speed: Number,
+}
+
+impl Bonuses {
+    fn apply(&mut self, effects: &[Effect], times: Number) {
+        for effect in effects {
+            let (bonus, amount) = match *effect {
+                Effect::MiningDrillProductivity(amount) => {
+                    (&mut self.mining_drill_productivity, amount)
+                }
+                Effect::LaboratorySpeed(amount) => (&mut self.laboratory_speed, amount),
+                Effect::LaboratoryProductivity(amount) => {
+                    (&mut self.laboratory_productivity, amount)
+                }
+                Effect::CharacterMiningSpeed(amount) => (&mut self.character_mining_speed, amount),
+                Effect::CharacterCraftingSpeed(amount) => {
+                    (&mut self.character_crafting_speed, amount)
+                }
+            };
+            *bonus += amount * times;
+        }
+    }
+}
+
+/// Surface daylight, parts of the cycle are fractions summing up to 1
+#[derive(Debug, Clone, Copy)]
+pub struct DayCycle {
+    pub length: Number<Seconds>,
+    pub day: Number,
+    pub dusk: Number,
+    pub night: Number,
+    pub dawn: Number,
+}
+
+impl Default for DayCycle {
+    /// Nauvis
+    fn default() -> Self {
+        Self {
+            length: Number::new(25000.0 / 60.0),
+            day: Number::new(0.5),
+            dusk: Number::new(0.2),
+            night: Number::new(0.1),
+            dawn: Number::new(0.2),
+        }
+    }
+}
+
+impl DayCycle {
+    /// Normalizes the parts to sum up to 1
+    pub fn new(
+        length: Number<Seconds>,
+        day: Number,
+        dusk: Number,
+        night: Number,
+        dawn: Number,
+    ) -> Self {
+        let total = day + dusk + night + dawn;
+        assert!(total.value() > 0.0, "Day cycle must have some length");
+        Self {
+            length,
+            day: day / total,
+            dusk: dusk / total,
+            night: night / total,
+            dawn: dawn / total,
+        }
+    }
+
+    /// Average fraction of peak solar output over the whole cycle
+    pub fn solar_fraction(&self) -> Number {
+        self.day + (self.dusk + self.dawn) / Number::from(2)
+    }
+
+    /// Joules of storage per watt of peak solar output
+    /// to keep a constant consumer going through the night
+    fn storage_per_watt(&self) -> Number {
+        // output ramps linearly during dusk and dawn,
+        // accumulators cover the night and the dark part of the ramps
+        let average = self.solar_fraction();
+        Number::new(self.length.value())
+            * (average * self.night + average * average / Number::from(2) * (self.dusk + self.dawn))
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 struct ExecutedStep {
     crafts: HashMap<Arc<str>, Number>,
     builds: HashMap<Arc<str>, Number>,
+    /// Change of the world's inventory
+    inventory: HashMap<Item, Number>,
     single_machine_time: HashMap<Arc<str>, Number<Seconds>>,
 }
 
@@ -36,24 +142,33 @@ pub struct Plan {
 }
 
 impl World {
-    pub fn new(mode: RecipeMode, science_multiplier: Number) -> anyhow::Result<Self> {
-        let data = Data::new(mode, science_multiplier)?;
+    /// `data` is loaded from `data_path` with `science_multiplier`
+    pub fn new(data_path: &std::path::Path, mut data: Data, science_multiplier: Number) -> Self {
+        data.set_solar_fraction(DayCycle::default().solar_fraction());
 
         let mut machines = HashMap::new();
         machines.insert(CHARACTER_MINING.into(), 1.into());
         machines.insert(CHARACTER_CRAFTING.into(), 1.into());
-        machines.insert(FREE_STUFF.into(), 1.into());
 
-        Ok(Self {
+        Self {
             no_thinking: true,
+            solver: Solver::Greedy,
             data: Arc::new(data),
+            data_path: data_path.to_owned(),
+            science_multiplier,
+            technology_price_multiplier: 1.into(),
             machines,
+            inventory: HashMap::new(),
+            day_cycle: DayCycle::default(),
+            reactor_neighbours: Number::new(0.0),
             preferred_fuel: HashMap::new(),
+            recipe_policy: RecipePolicy::default(),
             researches: HashSet::new(),
+            bonuses: Bonuses::default(),
             time: Number::new(0.0),
             total_crafts: HashMap::new(),
             total_machine_time: Number::new(0.0),
-        })
+        }
     }
 
     pub fn craft(&mut self, item: impl Into<Item>, amount: impl Into<Number>) {
@@ -78,17 +193,161 @@ impl World {
             .execute(self);
     }
 
+    /// Let placed machines work for `duration`,
+    /// producing as much of `item` as they can into the inventory
+    pub fn sleep(&mut self, duration: Number<Seconds>, item: Option<Item>) {
+        let Some(item) = item else {
+            self.time += duration;
+            log::info!("Time now is {:?}", self.time);
+            return;
+        };
+        // the machines make everything from scratch, stock already there is left alone
+        let mut idle = self.clone();
+        idle.inventory.clear();
+        // production is about linear in time, so measure a reference amount first
+        let reference_amount = Number::from(1000);
+        let reference_time = idle
+            .plan_craft(item.clone(), reference_amount)
+            .total_time(&idle);
+        if reference_time.value() == 0.0 {
+            panic!("{item:?} is produced instantly, can not sleep producing it");
+        }
+        let mut amount = reference_amount * (duration / reference_time).convert();
+        let mut step = idle.plan_craft(item.clone(), amount);
+        // energy is only crafted above a unit, so scale down until it fits
+        loop {
+            let step_time = step.total_time(&idle);
+            if step_time.value() <= duration.value() * (1.0 + 1e-9) {
+                break;
+            }
+            amount *= (duration / step_time).convert();
+            step = idle.plan_craft(item.clone(), amount);
+        }
+        step.log(self);
+        let time = self.time;
+        step.execute(self);
+        self.time = time + duration;
+        log::info!(
+            "Produced {amount:?} of {item:?} while sleeping, time now is {:?}",
+            self.time,
+        );
+    }
+
+    fn plan_craft(&self, item: Item, amount: Number) -> ExecutedStep {
+        let mut planner = StepPlanner::new(self);
+        planner.produce(item, amount);
+        planner.finalize()
+    }
+
+    pub fn preset(&self, name: &str) -> anyhow::Result<Preset> {
+        self.data
+            .presets
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown preset {name:?}"))
+    }
+
+    /// Reloads the data with the preset's recipe mode and tech price multiplier,
+    /// everything else about the world stays as it is
+    pub fn apply_preset(&mut self, name: &str) -> anyhow::Result<()> {
+        let preset = self.preset(name)?;
+        log::info!("Using preset {name:?}: {preset:?}");
+        let mut data = Data::new(
+            &self.data_path,
+            preset.recipe_mode,
+            preset.technology_price_multiplier * self.science_multiplier,
+        )?;
+        // machines with modules are registered on the fly, they do not depend on the mode
+        for (name, machine) in &self.data.machines {
+            if !data.machines.contains_key(name) {
+                data.machines.insert(name.clone(), machine.clone());
+            }
+        }
+        data.set_solar_fraction(self.day_cycle.solar_fraction());
+        self.data = Arc::new(data);
+        self.technology_price_multiplier = preset.technology_price_multiplier;
+        Ok(())
+    }
+
+    /// Multiplies the preset's tech price multiplier, does not replace it
+    pub fn set_science_multiplier(&mut self, science_multiplier: Number) {
+        self.science_multiplier = science_multiplier;
+        Arc::make_mut(&mut self.data)
+            .set_science_multiplier(science_multiplier * self.technology_price_multiplier);
+    }
+
+    pub fn launch_rocket(&mut self, amount: impl Into<Number>) {
+        let amount = amount.into();
+        self.craft(ROCKET_LAUNCH, amount);
+        // launches are not items, nothing to keep for the next one
+        self.inventory.remove(&ROCKET_LAUNCH.into());
+        log::info!("Launched {amount:?} rockets, time now is {:?}", self.time);
+    }
+
+    /// Solar panels produce their average output for the cycle
+    pub fn set_day_cycle(&mut self, day_cycle: DayCycle) {
+        self.day_cycle = day_cycle;
+        Arc::make_mut(&mut self.data).set_solar_fraction(day_cycle.solar_fraction());
+    }
+
+    /// Accumulators are not simulated, only sized for `peak` watts of new solar panels
+    fn log_accumulators_needed(&self, peak: Number) {
+        let storage = peak * self.day_cycle.storage_per_watt();
+        for accumulator in self.data.machines.values() {
+            if let MachineKind::Accumulator { buffer_capacity } = accumulator.kind {
+                log::info!(
+                    "Lasting the night takes {:?} of {:?}",
+                    storage / buffer_capacity,
+                    accumulator.name,
+                );
+            }
+        }
+    }
+
+    /// Water out of nowhere instead of offshore pumps
+    pub fn free_water(&mut self) {
+        self.machines.insert(FREE_STUFF.into(), 1.into());
+    }
+
+    pub fn give(&mut self, item: impl Into<Item>, amount: impl Into<Number>) {
+        *self.inventory.entry(item.into()).or_default() += amount.into();
+    }
+
     pub fn prefer_fuel(&mut self, category: FuelCategory, item: impl Into<Item>) {
         self.preferred_fuel.insert(category, item.into());
     }
 
+    pub fn prefer_recipe(&mut self, item: impl Into<Item>, recipe: impl Into<Arc<str>>) {
+        self.recipe_policy.prefer(item, recipe);
+    }
+
+    pub fn forbid_recipe(&mut self, pattern: impl Into<Arc<str>>, item: Option<Item>) {
+        self.recipe_policy.forbid(pattern, item);
+    }
+
+    pub fn unforbid_recipe(&mut self, pattern: &str) {
+        self.recipe_policy.unforbid(pattern);
+    }
+
     pub fn destroy_all(&mut self, machine: impl Into<Item>) {
         let machine = machine.into();
         self.machines.remove(machine.name());
     }
 
     pub fn unresearch(&mut self, research: impl Into<Arc<str>>) {
-        self.researches.remove(&research.into());
+        let research = research.into();
+        if !self.researches.remove(&research) {
+            return;
+        }
+        let data = self.data.clone();
+        let (base_name, level) = split_level(&research);
+        if let Some(research) = data
+            .researches
+            .get(&research)
+            .or_else(|| data.find_research_level(base_name, level?))
+        {
+            self.bonuses.apply(&research.effects, Number::from(-1));
+        }
     }
 
     pub fn research(&mut self, research: impl Into<Arc<str>>) {
@@ -102,19 +361,127 @@ impl World {
             self.research(dependency.clone());
         }
 
+        self.research_recipe(research.recipe.clone());
+        self.researches.insert(research.name.clone());
+        self.bonuses.apply(&research.effects, Number::from(1));
+        log::info!("researched {:?}", research.name);
+    }
+
+    /// Research `mining-productivity` level 12 means all the levels up to 12
+    pub fn research_level(&mut self, base_name: &str, level: usize) {
+        let data = self.data.clone();
+        let research = data
+            .find_research_level(base_name, level)
+            .unwrap_or_else(|| panic!("No research {base_name:?} of level {level}"));
+        if level == research.level {
+            self.research(research.name.clone());
+            return;
+        }
+        let level_name = research.level_name(level);
+        if self.researches.contains(&level_name) {
+            return;
+        }
+        self.research_level(base_name, level - 1);
+
+        let recipe = Arc::make_mut(&mut self.data).insert_research_recipe(&research.name, level);
+        self.research_recipe(recipe);
+        self.researches.insert(level_name.clone());
+        self.bonuses.apply(&research.effects, Number::from(1));
+        log::info!("researched {level_name:?}");
+    }
+
+    fn research_recipe(&mut self, recipe: Arc<str>) {
+        // labs are the only machines doing research, trigger technologies need none
+        let crafts = if self.data.recipes[&recipe].crafting_time.is_some() {
+            Number::from(1) / (Number::from(1) + self.bonuses.laboratory_productivity)
+        } else {
+            Number::from(1)
+        };
         self.planner()
             .add_tasks({
                 let mut tasks = Tasks::default();
-                tasks.craft_recipe.insert(
-                    format!("research {:?}", research.name).as_str().into(),
-                    1.into(),
-                );
+                tasks.craft_recipe.insert(recipe, crafts);
                 tasks
             })
             .think()
             .execute(self);
-        self.researches.insert(research.name.clone());
-        log::info!("researched {:?}", research.name);
+    }
+
+    /// Crafting speed including research bonuses and modules
+    fn machine_speed(&self, machine: &str, recipe: &Recipe) -> Number {
+        let machine = &self.data.machines[machine];
+        let bonus = match machine.kind {
+            MachineKind::Lab => self.bonuses.laboratory_speed,
+            MachineKind::CharacterMining => self.bonuses.character_mining_speed,
+            MachineKind::CharacterCrafting => self.bonuses.character_crafting_speed,
+            _ => Number::new(0.0),
+        };
+        let modules = machine.module_effects(&recipe.name);
+        machine.crafting_speed
+            * (Number::from(1) + bonus)
+            * std::cmp::max(Number::new(0.2), Number::from(1) + modules.speed)
+    }
+
+    fn machine_productivity(&self, machine: &str, recipe: &Recipe) -> Number {
+        let machine = &self.data.machines[machine];
+        let bonus = match machine.kind {
+            MachineKind::MiningDrill => self.bonuses.mining_drill_productivity,
+            MachineKind::Lab => self.bonuses.laboratory_productivity,
+            MachineKind::Reactor { neighbour_bonus } => neighbour_bonus * self.reactor_neighbours,
+            _ => Number::new(0.0),
+        };
+        if !recipe.allow_productivity {
+            return bonus;
+        }
+        bonus + machine.module_effects(&recipe.name).productivity
+    }
+
+    /// Place machines for free, with modules if specified
+    pub fn place(
+        &mut self,
+        machine: &str,
+        amount: Number,
+        modules: &ModuleSetup,
+    ) -> anyhow::Result<()> {
+        let name = if modules.modules.is_empty() && modules.beacons == 0 {
+            machine.into()
+        } else {
+            Arc::make_mut(&mut self.data).machine_with_modules(machine, modules)?
+        };
+        *self.machines.entry(name).or_default() += amount;
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self, recipe: &str) -> bool {
+        self.data
+            .locked_recipes
+            .get(recipe)
+            .is_none_or(|technologies| {
+                technologies
+                    .iter()
+                    .any(|technology| self.researches.contains(technology))
+            })
+    }
+
+    /// Explain why the item can not be crafted, if its because of research
+    fn locked_reason(&self, item: &Item) -> Option<String> {
+        let locked = self
+            .data
+            .recipes
+            .values()
+            .filter(|recipe| recipe.results.contains_key(item))
+            .filter(|recipe| !self.is_unlocked(&recipe.name))
+            .map(|recipe| {
+                format!(
+                    "{:?} is unlocked by researching one of {:?}",
+                    recipe.name, self.data.locked_recipes[&recipe.name],
+                )
+            })
+            .collect_vec();
+        if locked.is_empty() {
+            return None;
+        }
+        Some(locked.join(", "))
     }
 
     pub fn planner(&self) -> Planner<'_> {
@@ -215,7 +582,7 @@ impl Tasks {
     fn execute(&self, world: &mut World, log: bool) {
         let mut planner = StepPlanner::new(world);
         for (item, amount) in &self.craft {
-            planner.craft(item.clone(), *amount);
+            planner.produce(item.clone(), *amount);
         }
         for (recipe, amount) in &self.craft_recipe {
             planner.craft_recipe(recipe.clone(), *amount);
@@ -238,75 +605,122 @@ fn find_recipe_for(world: &World, item: impl Into<Item>) -> Option<Arc<str>> {
     let item = item.into();
 
     if let Item::Energy {
-        fuel_category: Some(category),
-        energy_type: EnergyType::Burner,
-    } = item
+        fuel_categories: Some(categories),
+        energy_type: EnergyType::Burner | EnergyType::Fluid,
+    } = &item
     {
-        let fuel_item = world
-            .preferred_fuel
-            .get(&category)
-            .unwrap_or_else(|| panic!("No preferred fuel set for {category:?}"));
-        // TODO this format is copypasta
-        return Some(format!("{:?} {:?} burnable fuel energy", fuel_item.name(), category).into());
+        let fuel_item = categories
+            .iter()
+            .find_map(|category| world.preferred_fuel.get(category))
+            .unwrap_or_else(|| panic!("No preferred fuel set for any of {categories:?}"));
+        return Some(burnable_fuel_energy_recipe(fuel_item.name(), categories));
     }
 
+    if let Some(recipe) = world
+        .recipe_policy
+        .preferred(&item)
+        .and_then(|recipe| world.data.recipes.get(recipe))
+        .filter(|recipe| can_craft(world, recipe, &item))
+    {
+        return Some(recipe.name.clone());
+    }
+
+    // generators for steam of different temperatures, prefer the steam that can be made now
+    let obtainable = |item: &Item| match item {
+        Item::Fluid { .. } => {
+            world.inventory.contains_key(item) || recipe_candidates(world, item).next().is_some()
+        }
+        _ => true,
+    };
+    recipe_candidates(world, &item)
+        .max_by_key(|recipe| {
+            (
+                matches!(recipe.category, Category::Free),
+                recipe.ingredients.keys().all(obtainable),
+            )
+        })
+        .map(|recipe| recipe.name.clone())
+}
+
+/// All the recipes that could be used to craft the item and are not forbidden,
+/// preferences only matter for [`find_recipe_for`]
+fn recipe_candidates<'a: 'b, 'b>(
+    world: &'a World,
+    item: &'b Item,
+) -> impl Iterator<Item = &'a Recipe> + 'b {
     // TODO cache maybe?
     world
         .data
         .recipes
         .values()
-        .filter(|recipe| recipe.results.contains_key(&item))
-        .filter(|recipe| {
-            world.machines.keys().any(|machine| {
-                world.data.machines[machine]
-                    .categories
-                    .contains(&recipe.category)
-            })
+        .filter(|recipe| can_craft(world, recipe, item))
+        .filter(|recipe| !world.recipe_policy.is_forbidden(&recipe.name, item))
+}
+
+fn can_craft(world: &World, recipe: &Recipe, item: &Item) -> bool {
+    recipe.results.contains_key(item)
+        && world.is_unlocked(&recipe.name)
+        && world.machines.keys().any(|machine| {
+            world.data.machines[machine]
+                .categories
+                .contains(&recipe.category)
         })
-        // mine rocks for stone, but not for coal
-        .filter(|recipe| !(item.is("coal") && recipe.name.contains("pickaxe")))
-        .filter(|recipe| !recipe.name.contains("barrel"))
-        .filter(|recipe| &*recipe.name != "coal-liquefaction")
-        .max_by_key(|recipe| {
-            (
-                matches!(recipe.category, Category::Free),
-                &*recipe.name == "advanced-oil-processing",
-            )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solver {
+    /// Recursively expand every item using the best recipe for it
+    Greedy,
+    /// Solve a linear program over all usable recipes, minimizing machine time
+    Lp,
+}
+
+impl std::str::FromStr for Solver {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "greedy" => Self::Greedy,
+            "lp" => Self::Lp,
+            _ => anyhow::bail!("Unknown solver {s:?}, expected greedy or lp"),
         })
-        .map(|recipe| recipe.name.clone())
+    }
 }
 
 struct StepPlanner<'a> {
     world: &'a World,
     executed: ExecutedStep,
+    /// Items to be provided by the linear program
+    demand: HashMap<Item, Number>,
+    /// Newly crafted items that can not be taken from the inventory
+    fresh: HashSet<Item>,
+    /// Items being crafted right now, outermost first
+    crafting: Vec<Item>,
+    /// Energy the machines need for what was crafted so far
+    energy: HashMap<Item, Number>,
 }
 
 impl<'a> StepPlanner<'a> {
     fn finalize(mut self) -> ExecutedStep {
-        let data = self.world.data.clone();
-        let mut done = false;
-        let mut total_times = HashMap::<Arc<str>, Number<Seconds>>::new();
-        while !done {
-            done = true;
-            for (machine_name, single_machine_time) in
-                std::mem::take(&mut self.executed.single_machine_time)
-            {
-                *total_times.entry(machine_name.clone()).or_default() += single_machine_time;
-                let machine = &data.machines[&machine_name];
-                for (energy_item, &usage) in &machine.energy_usage {
-                    let energy_amount = usage * single_machine_time.convert::<()>();
-                    if energy_amount.value() < 1.0 {
-                        continue;
-                    }
-                    done = false;
-                    self.craft(energy_item.clone(), energy_amount);
+        if self.world.solver == Solver::Lp {
+            self.solve_lp();
+            return self.executed;
+        }
+        // making energy takes energy too, until it is negligible
+        while !self.energy.is_empty() {
+            for (energy_item, energy_amount) in std::mem::take(&mut self.energy) {
+                if energy_amount.value() >= 1.0 {
+                    self.craft(energy_item, energy_amount);
                 }
             }
         }
-        self.executed.single_machine_time = total_times;
         self.executed
     }
     fn build(&mut self, machine: Item, amount: Number) {
+        if find_recipe_for(self.world, machine.clone()).is_none() {
+            if let Some(reason) = self.world.locked_reason(&machine) {
+                panic!("Can not build {machine:?} yet: {reason}");
+            }
+        }
         *self
             .executed
             .builds
@@ -314,16 +728,80 @@ impl<'a> StepPlanner<'a> {
             .or_default() += amount;
         self.craft(machine, amount);
     }
+    /// Craft new items, putting them into the inventory
+    fn produce(&mut self, item: Item, amount: Number) {
+        self.craft_new(item.clone(), amount);
+        self.put(item, amount);
+    }
+    fn available(&self, item: &Item) -> Number {
+        self.world.inventory.get(item).copied().unwrap_or_default()
+            + self
+                .executed
+                .inventory
+                .get(item)
+                .copied()
+                .unwrap_or_default()
+    }
+    fn put(&mut self, item: Item, amount: Number) {
+        *self.executed.inventory.entry(item).or_default() += amount;
+    }
+    /// Take items from the inventory, crafting whatever is missing
     fn craft(&mut self, item: Item, amount: Number) {
-        let recipe = find_recipe_for(self.world, item.clone())
-            .unwrap_or_else(|| panic!("Could not find recipe for {item:?}"));
+        if self.world.solver == Solver::Lp {
+            *self.demand.entry(item).or_default() += amount;
+            return;
+        }
+        let mut amount = amount;
+        if let Item::Item { .. } | Item::Fluid { .. } = item {
+            let taken = std::cmp::min(amount, self.available(&item));
+            if taken.value() > 0.0 {
+                self.put(item.clone(), -taken);
+                amount -= taken;
+            }
+        }
+        if amount.value() > 0.0 {
+            self.craft_new(item, amount);
+        }
+    }
+    fn craft_new(&mut self, item: Item, amount: Number) {
+        if self.world.solver == Solver::Lp {
+            *self.demand.entry(item.clone()).or_default() += amount;
+            self.fresh.insert(item);
+            return;
+        }
+        let recipe = find_recipe_for(self.world, item.clone()).unwrap_or_else(|| {
+            match self.world.locked_reason(&item) {
+                Some(reason) => panic!("Could not craft {item:?}: {reason}"),
+                None => panic!("Could not find recipe for {item:?}"),
+            }
+        });
         log::trace!("craft {item:?} ({amount:?}) using {recipe:#?}");
 
         let recipe = &self.world.data.recipes[&recipe];
-        let crafts = amount / recipe.results[&item];
-        // TODO: im ignoring byproducts
+        let net_yield = recipe.net_yield(&item, self.recipe_productivity(recipe));
+        if net_yield.value() <= 0.0 {
+            panic!(
+                "{:?} does not produce more {item:?} than it consumes",
+                recipe.name
+            );
+        }
+        let crafts = amount / net_yield;
 
+        if self.crafting.contains(&item) {
+            let cycle = self
+                .crafting
+                .iter()
+                .chain([&item])
+                .map(|item| format!("{item:?}"));
+            panic!("Recipe cycle: {}", cycle.format(" -> "));
+        }
+        self.crafting.push(item.clone());
+        // all the results (including byproducts) go into the inventory first
         self.craft_recipe(recipe.name.clone(), crafts);
+        self.crafting.pop();
+        if let Item::Item { .. } | Item::Fluid { .. } = item {
+            self.put(item, -amount);
+        }
     }
     fn craft_recipe(&mut self, recipe: Arc<str>, crafts: Number) {
         let data = self.world.data.clone();
@@ -333,47 +811,239 @@ impl<'a> StepPlanner<'a> {
             .unwrap_or_else(|| panic!("recipe {recipe:?} not found"));
         *self.executed.crafts.entry(recipe.name.clone()).or_default() += crafts;
 
-        for (ingredient, &ingredient_amount) in &recipe.ingredients {
-            self.craft(ingredient.clone(), ingredient_amount * crafts);
+        let productivity = self.recipe_productivity(recipe);
+        // items the recipe gives back only count with their net amount
+        for ingredient in recipe.ingredients.keys() {
+            let needed = -recipe.net_yield(ingredient, productivity) * crafts;
+            if needed.value() > 0.0 {
+                self.craft(ingredient.clone(), needed);
+            }
+        }
+        for result in recipe.results.keys() {
+            if let Item::Item { .. } | Item::Fluid { .. } = result {
+                let produced = recipe.net_yield(result, productivity) * crafts;
+                if produced.value() > 0.0 {
+                    self.put(result.clone(), produced);
+                }
+            }
         }
 
+        self.account_machine_time(recipe, crafts);
+    }
+    /// Placed machines that can craft the recipe, with their amounts
+    fn machines_used(&self, recipe: &Recipe) -> Vec<(Arc<str>, Number)> {
+        let data = &self.world.data;
+        self.world
+            .machines
+            .iter()
+            .filter(|&(name, _)| data.machines[name].categories.contains(&recipe.category))
+            .map(|(name, &count)| (name.clone(), count))
+            .collect()
+    }
+    fn total_speed(&self, recipe: &Recipe, machines_used: &[(Arc<str>, Number)]) -> Number {
+        machines_used
+            .iter()
+            .fold(Number::from(0), |sum, (machine_name, machine_count)| {
+                sum + self.world.machine_speed(machine_name, recipe) * *machine_count
+            })
+    }
+    /// Average productivity bonus of the machines crafting the recipe
+    fn recipe_productivity(&self, recipe: &Recipe) -> Number {
+        let machines_used = self.machines_used(recipe);
+        let total_speed = self.total_speed(recipe, &machines_used);
+        if total_speed.value() == 0.0 {
+            return Number::new(0.0);
+        }
+        machines_used
+            .iter()
+            .fold(Number::from(0), |sum, (machine_name, machine_count)| {
+                sum + self.world.machine_speed(machine_name, recipe)
+                    * *machine_count
+                    * self.world.machine_productivity(machine_name, recipe)
+            })
+            / total_speed
+    }
+    fn account_machine_time(&mut self, recipe: &Recipe, crafts: Number) {
         if let Some(recipe_crafting_time) = recipe.crafting_time {
-            let machines_used = self
-                .world
-                .machines
-                .iter()
-                .filter(|&(name, _)| data.machines[name].categories.contains(&recipe.category));
-
-            let total_speed = machines_used.clone().fold(
-                Number::from(0),
-                |sum, (machine_name, &machine_count)| {
-                    sum + data.machines[machine_name].crafting_speed * machine_count
-                },
-            );
+            let machines_used = self.machines_used(recipe);
+            let total_speed = self.total_speed(recipe, &machines_used);
 
             if total_speed.value() == 0.0 {
                 panic!("No machines that can craft {recipe:?}");
             }
 
-            for (machine_name, &machine_count) in machines_used {
-                let crafts = crafts * data.machines[machine_name].crafting_speed * machine_count
-                    / total_speed;
+            for (machine_name, machine_count) in machines_used {
+                let speed = self.world.machine_speed(&machine_name, recipe);
+                let crafts = crafts * speed * machine_count / total_speed;
 
-                let single_machine_time =
-                    crafts * recipe_crafting_time / data.machines[machine_name].crafting_speed;
+                let single_machine_time = crafts * recipe_crafting_time / speed;
+                for (energy_item, usage) in
+                    self.world.data.machines[&machine_name].energy_usage_for(&recipe.name)
+                {
+                    *self.energy.entry(energy_item).or_default() += usage * single_machine_time;
+                }
                 *self
                     .executed
                     .single_machine_time
-                    .entry(machine_name.clone())
+                    .entry(machine_name)
                     .or_default() += single_machine_time.convert::<Seconds>();
             }
         }
     }
+    /// Energy used by the machines per single craft of the recipe
+    fn recipe_energy(&self, recipe: &Recipe) -> HashMap<Item, Number> {
+        let mut energy = HashMap::new();
+        let Some(crafting_time) = recipe.crafting_time else {
+            return energy;
+        };
+        let machines_used = self.machines_used(recipe);
+        let total_speed = self.total_speed(recipe, &machines_used);
+        for (machine_name, machine_count) in machines_used {
+            for (energy_item, usage) in
+                self.world.data.machines[&machine_name].energy_usage_for(&recipe.name)
+            {
+                *energy.entry(energy_item).or_default() +=
+                    usage * machine_count * crafting_time / total_speed;
+            }
+        }
+        energy
+    }
+    /// Time it takes to craft the recipe once, using all the machines
+    fn recipe_cost(&self, recipe: &Recipe) -> Number {
+        let Some(crafting_time) = recipe.crafting_time else {
+            return Number::new(0.0);
+        };
+        crafting_time / self.total_speed(recipe, &self.machines_used(recipe))
+    }
+    fn solve_lp(&mut self) {
+        let data = self.world.data.clone();
+
+        // energy for the recipes that were crafted directly,
+        // the program accounts for the energy of its own recipes
+        for (energy_item, amount) in std::mem::take(&mut self.energy) {
+            *self.demand.entry(energy_item).or_default() += amount;
+        }
+        let demand = std::mem::take(&mut self.demand);
+
+        let mut items = Vec::<Item>::new();
+        let mut item_index = HashMap::<Item, usize>::new();
+        let mut recipes = Vec::<(&Recipe, HashMap<Item, Number>)>::new();
+        let mut used_recipes = HashSet::<Arc<str>>::new();
+        let mut queue: Vec<Item> = demand.keys().cloned().collect();
+        while let Some(item) = queue.pop() {
+            if item_index.contains_key(&item) {
+                continue;
+            }
+            item_index.insert(item.clone(), items.len());
+            items.push(item.clone());
+            let candidates: Vec<&Recipe> = match item {
+                Item::Energy {
+                    energy_type: EnergyType::Burner | EnergyType::Fluid,
+                    ..
+                } => find_recipe_for(self.world, item.clone())
+                    .map(|recipe| &data.recipes[&recipe])
+                    .into_iter()
+                    .collect(),
+                _ => recipe_candidates(self.world, &item).collect(),
+            };
+            if candidates.is_empty() {
+                log::debug!("No recipes for {item:?}");
+            }
+            for recipe in candidates {
+                if !used_recipes.insert(recipe.name.clone()) {
+                    continue;
+                }
+                let energy = self.recipe_energy(recipe);
+                queue.extend(
+                    recipe
+                        .ingredients
+                        .keys()
+                        .chain(recipe.results.keys())
+                        .chain(energy.keys())
+                        .cloned(),
+                );
+                recipes.push((recipe, energy));
+            }
+        }
+
+        let mut problem = lp::Problem {
+            cost: recipes
+                .iter()
+                .map(|(recipe, _)| self.recipe_cost(recipe).value())
+                .collect(),
+            constraints: vec![vec![0.0; recipes.len()]; items.len()],
+            bounds: items
+                .iter()
+                .map(|item| {
+                    let supply = if self.fresh.contains(item) {
+                        0.0
+                    } else {
+                        self.available(item).value().max(0.0)
+                    };
+                    demand.get(item).map_or(0.0, |amount| amount.value()) - supply
+                })
+                .collect(),
+        };
+        for (j, (recipe, energy)) in recipes.iter().enumerate() {
+            let productivity = self.recipe_productivity(recipe);
+            for result in recipe.results.keys() {
+                problem.constraints[item_index[result]][j] +=
+                    recipe.result_amount(result, productivity).value();
+            }
+            for (ingredient, amount) in recipe.ingredients.iter().chain(energy) {
+                problem.constraints[item_index[ingredient]][j] -= amount.value();
+            }
+        }
+        let solution = lp::minimize(&problem).unwrap_or_else(|e| {
+            let missing = items
+                .iter()
+                .filter(|item| {
+                    !recipes
+                        .iter()
+                        .any(|(recipe, _)| recipe.results.contains_key(item))
+                })
+                .collect_vec();
+            panic!("{e}, items without recipes: {missing:?}")
+        });
+
+        for ((recipe, _), crafts) in recipes.iter().zip(solution) {
+            if crafts <= 0.0 {
+                continue;
+            }
+            let crafts = Number::new(crafts);
+            log::trace!("lp: {crafts:?} crafts of {:?}", recipe.name);
+            *self.executed.crafts.entry(recipe.name.clone()).or_default() += crafts;
+            let productivity = self.recipe_productivity(recipe);
+            for result in recipe.results.keys() {
+                if let Item::Item { .. } | Item::Fluid { .. } = result {
+                    self.put(
+                        result.clone(),
+                        recipe.result_amount(result, productivity) * crafts,
+                    );
+                }
+            }
+            for (ingredient, &amount) in &recipe.ingredients {
+                if let Item::Item { .. } | Item::Fluid { .. } = ingredient {
+                    self.put(ingredient.clone(), -amount * crafts);
+                }
+            }
+            self.account_machine_time(recipe, crafts);
+        }
+        for (item, amount) in demand {
+            if let Item::Item { .. } | Item::Fluid { .. } = item {
+                self.put(item, -amount);
+            }
+        }
+    }
 
     fn new(world: &'a World) -> Self {
         Self {
             world,
             executed: ExecutedStep::default(),
+            demand: HashMap::new(),
+            fresh: HashSet::new(),
+            crafting: Vec::new(),
+            energy: HashMap::new(),
         }
     }
 }
@@ -409,6 +1079,11 @@ impl ExecutedStep {
             log::debug!("Built {amount:?} of {machine:?}");
             *world.machines.entry(machine.clone()).or_default() += amount;
         }
+        for (item, &amount) in &self.inventory {
+            log::debug!("Inventory {item:?} changed by {amount:?}");
+            *world.inventory.entry(item.clone()).or_default() += amount;
+        }
+        world.inventory.retain(|_, amount| amount.value() > 1e-9);
         log::debug!("Machine times: {times:#?}");
         for time in times.values().copied() {
             world.total_machine_time += time;
@@ -419,9 +1094,19 @@ impl ExecutedStep {
         log::debug!("Time now is {:?}", world.time);
     }
 
+    fn total_time(&self, world: &World) -> Number<Seconds> {
+        self.machine_times(world)
+            .into_values()
+            .max()
+            .unwrap_or_default()
+    }
+
     fn log(&self, world: &World) {
         for (machine, amount) in &self.builds {
             log::info!("Built {amount:?} of {machine:?}");
+            if let MachineKind::SolarPanel { production } = world.data.machines[machine].kind {
+                world.log_accumulators_needed(production * *amount);
+            }
         }
         let mut times = self.machine_times(world).into_iter().collect_vec();
         times.sort_by_key(|(_, time)| *time);
@@ -433,3 +1118,136 @@ impl ExecutedStep {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(name: &str, kind: MachineKind, category: Category) -> Machine {
+        Machine {
+            name: name.into(),
+            kind,
+            modules: MachineModules::new(0),
+            categories: HashSet::from_iter([category]),
+            energy_usage: HashMap::new(),
+            crafting_speed: 1.into(),
+        }
+    }
+
+    fn recipe(name: &str, ingredients: &[(&str, i64)], crafting_time: f64) -> (Arc<str>, Recipe) {
+        let recipe = Recipe {
+            name: name.into(),
+            category: Category::Craft("crafting".into()),
+            ingredients: ingredients
+                .iter()
+                .map(|&(item, amount)| (item.into(), amount.into()))
+                .collect(),
+            results: HashMap::from_iter([(name.into(), 1.into())]),
+            allow_productivity: true,
+            catalysts: HashMap::new(),
+            crafting_time: Some(crafting_time.into()),
+        };
+        (recipe.name.clone(), recipe)
+    }
+
+    /// Plates from nothing, gears from plates, assemblers from gears once `automation` is researched
+    fn world() -> World {
+        let crafting = Category::Craft("crafting".into());
+        let data = Data {
+            recipes: HashMap::from_iter([
+                recipe("iron-plate", &[], 1.0),
+                recipe("iron-gear-wheel", &[("iron-plate", 2)], 1.0),
+                recipe("assembling-machine", &[("iron-gear-wheel", 5)], 0.5),
+            ]),
+            machines: [
+                machine(
+                    CHARACTER_MINING,
+                    MachineKind::CharacterMining,
+                    Category::PickaxeMining,
+                ),
+                machine(
+                    CHARACTER_CRAFTING,
+                    MachineKind::CharacterCrafting,
+                    crafting.clone(),
+                ),
+                machine("assembling-machine", MachineKind::Other, crafting),
+            ]
+            .into_iter()
+            .map(|machine| (machine.name.clone(), machine))
+            .collect(),
+            researches: HashMap::new(),
+            modules: HashMap::new(),
+            beacons: HashMap::new(),
+            presets: HashMap::new(),
+            science_multiplier: 1.into(),
+            locked_recipes: HashMap::from_iter([(
+                "assembling-machine".into(),
+                vec!["automation".into()],
+            )]),
+        };
+        World::new(std::path::Path::new(crate::dump::FILE_NAME), data, 1.into())
+    }
+
+    #[test]
+    #[should_panic(expected = "Can not build Item { name: \"assembling-machine\" } yet")]
+    fn build_locked() {
+        world().build("assembling-machine", 1);
+    }
+
+    #[test]
+    fn build_unlocked() {
+        let mut world = world();
+        world.researches.insert("automation".into());
+        world.build("assembling-machine", 1);
+        assert_eq!(world.machines["assembling-machine"], 1.into());
+    }
+
+    #[test]
+    fn productivity_only_where_allowed() {
+        let mut world = world();
+        let data = Arc::make_mut(&mut world.data);
+        data.machines
+            .get_mut("assembling-machine")
+            .unwrap()
+            .modules
+            .base_effects
+            .productivity = Number::new(0.5);
+        data.recipes
+            .get_mut("iron-gear-wheel")
+            .unwrap()
+            .allow_productivity = false;
+        let productivity = |recipe: &str| {
+            world
+                .machine_productivity("assembling-machine", &world.data.recipes[recipe])
+                .value()
+        };
+        assert_eq!(productivity("iron-plate"), 0.5);
+        assert_eq!(productivity("iron-gear-wheel"), 0.0);
+    }
+
+    #[test]
+    fn sleep_stockpiles_machine_throughput() {
+        let mut world = world();
+        world
+            .place("assembling-machine", 2.into(), &ModuleSetup::default())
+            .unwrap();
+        world.give("iron-plate", 1000);
+        // 3 crafting speed, 3 seconds of crafting per gear
+        world.sleep(Number::new(100.0), Some("iron-gear-wheel".into()));
+        let gears = world.inventory[&"iron-gear-wheel".into()].value();
+        assert!((gears - 100.0).abs() < 1e-6, "{gears}");
+        assert_eq!(world.inventory[&"iron-plate".into()], 1000.into());
+        assert_eq!(world.time.value(), 100.0);
+    }
+
+    #[test]
+    fn launches_leave_nothing_behind() {
+        let mut world = world();
+        let (name, launch) = recipe(ROCKET_LAUNCH, &[("iron-plate", 10)], 1.0);
+        Arc::make_mut(&mut world.data).recipes.insert(name, launch);
+        world.launch_rocket(1);
+        world.launch_rocket(1);
+        assert!(!world.inventory.contains_key(&ROCKET_LAUNCH.into()));
+        assert_eq!(world.total_crafts[ROCKET_LAUNCH], 2.into());
+    }
+}